@@ -0,0 +1,110 @@
+use clap::{Args, Parser, Subcommand};
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[derive(Parser, Debug)]
+#[command(version, about = "Global Morse code interpreter")]
+pub struct Cli {
+    /// config file, searched in standard locations if not set (see `morse::discover::config_paths`)
+    #[arg(long, short, global = true, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// overrides `time_to_long_press` of config
+    #[arg(long, global = true, value_name = "MS", value_parser = parse_millis)]
+    pub time_to_long_press: Option<Duration>,
+
+    /// overrides `accept_sequence_delay` of config
+    #[arg(long, global = true, value_name = "MS", value_parser = parse_millis)]
+    pub accept_sequence_delay: Option<Duration>,
+
+    /// overrides `listen_delay` of config
+    #[arg(long, global = true, value_name = "MS", value_parser = parse_millis)]
+    pub listen_delay: Option<Duration>,
+
+    /// lang to start with (or to encode and decode with)
+    #[arg(long, short, global = true)]
+    pub lang: Option<String>,
+
+    /// `run` writes sidetone to WAV file (on exit) instead of playing it
+    #[arg(long, global = true, value_name = "PATH")]
+    pub sidetone_wav: Option<PathBuf>,
+
+    /// `run` writes key presses and releases to JSON lines file, see `replay`
+    #[arg(long, global = true, value_name = "PATH")]
+    pub record: Option<PathBuf>,
+
+    /// `run` polls keys every `listen_delay` instead of waiting for key events
+    #[arg(long, global = true)]
+    pub poll_keys: bool,
+
+    /// `run` plays no sidetone
+    #[arg(long, global = true)]
+    pub no_sidetone: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// listen main key and type decoded text (default)
+    Run,
+    /// report shadowed, duplicate and conflicting definitions
+    CheckConfig {
+        /// config file, `--config` or discovered one if not set
+        file: Option<PathBuf>,
+    },
+    /// print text as dots and dashes, letters are separated by spaces and words by ` / `
+    Encode {
+        text: String,
+        #[command(flatten)]
+        audio: AudioArgs,
+    },
+    /// print text of dots and dashes separated like `encode` output,
+    /// unknown sequences are reported with their positions
+    Decode {
+        morse: String,
+        /// text of unknown sequences
+        #[arg(long, default_value = "?")]
+        placeholder: String,
+    },
+    /// print text of CW recording
+    DecodeWav {
+        file: PathBuf,
+        /// lowest tone frequency in Hz
+        #[arg(long, default_value_t = 300.0)]
+        min_frequency: f64,
+        /// highest tone frequency in Hz
+        #[arg(long, default_value_t = 1200.0)]
+        max_frequency: f64,
+    },
+    /// print text of `--record` file decoded with current config
+    Replay { file: PathBuf },
+    /// print langs of config
+    ListLangs,
+}
+
+#[derive(Args, Debug)]
+pub struct AudioArgs {
+    /// write audio to WAV file instead
+    #[arg(long, value_name = "PATH")]
+    pub wav: Option<PathBuf>,
+    /// character speed of audio, `timing` of config if not set
+    #[arg(long)]
+    pub wpm: Option<f64>,
+    /// effective speed of audio
+    #[arg(long)]
+    pub farnsworth_wpm: Option<f64>,
+    /// tone frequency of audio in Hz, `tone` of config if not set
+    #[arg(long)]
+    pub frequency: Option<f64>,
+    /// tone volume of audio, 0 to 1
+    #[arg(long)]
+    pub volume: Option<f64>,
+}
+
+fn parse_millis(s: &str) -> Result<Duration, String> {
+    s.parse::<u64>()
+        .map(Duration::from_millis)
+        .map_err(|err| format!("expected milliseconds: {}", err))
+}
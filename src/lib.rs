@@ -0,0 +1,3 @@
+mod morse;
+
+pub use crate::morse::*;
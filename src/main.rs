@@ -1,100 +1,361 @@
-use enigo::KeyboardControllable;
+use clap::Parser;
+use cli::{AudioArgs, Cli, Command};
+use morse::sidetone::{NullSidetone, WavSidetone};
+use morse::{audio, cw};
+use morse::{Config, ConfigOverrides, ConfigSource, ConfigWatcher, KeySource, OutputSink};
+use morse::{RecordingKeySource, SidetoneSink, SystemClock, Timing};
 
-pub mod morse;
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::Path;
+
+mod cli;
 
 fn main() {
-    let config = std::fs::read("other/config.toml").unwrap();
-    let config = std::str::from_utf8(&config).unwrap();
-    let config = toml::from_str::<morse::ConfigSerde>(config).unwrap();
-    let config: morse::Config = config.try_into().unwrap();
-    morse::listen_loop(&config, event_handler);
+    let cli = Cli::parse();
+
+    let exit_code = match &cli.command {
+        None | Some(Command::Run) => run(&cli),
+        Some(Command::CheckConfig { file }) => check_config(&match file {
+            Some(file) => ConfigSource::File(file.clone()),
+            None => config_source(&cli),
+        }),
+        Some(Command::Encode { text, audio }) => encode(&cli, text, audio),
+        Some(Command::Decode { morse, placeholder }) => decode(&cli, morse, placeholder),
+        Some(Command::DecodeWav {
+            file,
+            min_frequency,
+            max_frequency,
+        }) => decode_wav(&cli, file, *min_frequency, *max_frequency),
+        Some(Command::Replay { file }) => replay(&cli, file),
+        Some(Command::ListLangs) => list_langs(&cli),
+    };
+    std::process::exit(exit_code);
+}
+
+/// `--config` file, first found one of `morse::discover::config_paths` otherwise
+fn config_source(cli: &Cli) -> ConfigSource {
+    match &cli.config {
+        Some(path) => ConfigSource::File(path.clone()),
+        None => morse::discover::find_config(),
+    }
 }
 
-fn key_click(enigo: &mut enigo::Enigo, layout_key: morse::ConfigLayoutKey, is_upper: bool) {
-    if is_upper {
-        enigo.key_down(enigo::Key::Shift);
+fn config_overrides(cli: &Cli) -> ConfigOverrides {
+    ConfigOverrides {
+        time_to_long_press: cli.time_to_long_press,
+        accept_sequence_delay: cli.accept_sequence_delay,
+        listen_delay: cli.listen_delay,
+        lang: cli.lang.clone(),
     }
-    match layout_key.lower {
-        enigo::Key::Layout(layout) => {
-            enigo.key_sequence(&layout.to_string());
+}
+
+/// loads config with command line overrides, exits on error
+fn load_config(cli: &Cli) -> Config {
+    let mut config = match Config::load_from(&config_source(cli)) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            std::process::exit(1);
         }
-        code => {
-            enigo.key_click(code);
+    };
+    if let Some(lang) = &cli.lang {
+        if !config.langs.contains_key(lang) {
+            eprintln!("error: lang {:?} is not defined in config", lang);
+            std::process::exit(1);
         }
     }
-    if is_upper {
-        enigo.key_up(enigo::Key::Shift);
+    if let Err(err) = config_overrides(cli).apply(&mut config) {
+        eprintln!("error: {}", err);
+        std::process::exit(1);
     }
+    config
 }
 
-fn event_handler(event: morse::InputEvent, state: &mut morse::InputState) {
-    use morse::{InputEvent, MorseKey};
-    use std::thread::spawn;
-    use windows::Win32::System::Diagnostics::Debug::Beep;
+fn run(cli: &Cli) -> i32 {
+    let source = config_source(cli);
+    println!("Config: {}", source);
+    let config = load_config(cli);
+    let mut watcher = ConfigWatcher::new(&source, config_overrides(cli));
+    let key_source = match key_source(cli, &config) {
+        Ok(key_source) => key_source,
+        Err(err) => {
+            eprintln!("error: failed to open key source: {}", err);
+            return 1;
+        }
+    };
+    let mut key_source: Box<dyn KeySource> = match &cli.record {
+        Some(path) => match File::create(path) {
+            Ok(file) => Box::new(RecordingKeySource::new(
+                key_source,
+                BufWriter::new(file),
+                SystemClock,
+            )),
+            Err(err) => {
+                eprintln!("error: failed to create {}: {}", path.display(), err);
+                return 1;
+            }
+        },
+        None => Box::new(key_source),
+    };
+    let mut sink = output_sink();
+    let mut sidetone = sidetone_sink(cli, &config);
+    let result = morse::listen_loop(
+        &config,
+        Some(&mut watcher),
+        key_source.as_mut(),
+        &SystemClock,
+        |event, state| event_handler(sink.as_mut(), sidetone.as_mut(), event, state),
+    );
+    let mut code = 0;
+    if let Err(err) = result {
+        eprintln!("error: key source failed: {}", err);
+        code = 1;
+    }
+    if let Err(err) = sidetone.finish() {
+        eprintln!("error: failed to write sidetone: {}", err);
+        code = 1;
+    }
+    code
+}
 
-    const DOT_KEY: &str = ".";
-    const DOT_KEY_UPPER: &str = ">";
-    const DASH_KEY: &str = "-";
-    const DASH_KEY_UPPER: &str = "_";
-    const DOT_BEEP_FREQ: u32 = 500;
-    const DASH_BEEP_FREQ: u32 = 800;
-    const DOT_BEEP_DURATION: u32 = 60;
-    const DASH_BEEP_DURATION: u32 = 400;
+fn encode(cli: &Cli, text: &str, audio: &AudioArgs) -> i32 {
+    let config = load_config(cli);
+    let Some(lang) = config.start_lang() else {
+        eprintln!("error: no langs defined in config");
+        return 1;
+    };
+    let sequences = match config.encode(lang, text) {
+        Ok(sequences) => sequences,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            return 1;
+        }
+    };
+    let Some(wav) = &audio.wav else {
+        println!("{}", morse::encode::format_sequences(&sequences));
+        return 0;
+    };
 
-    let mut enigo = enigo::Enigo::new();
+    let mut timing = config.timing();
+    if let Some(wpm) = audio.wpm {
+        timing = Timing {
+            wpm,
+            farnsworth_wpm: None,
+        };
+    }
+    if let Some(farnsworth_wpm) = audio.farnsworth_wpm {
+        timing.farnsworth_wpm = Some(farnsworth_wpm);
+    }
+    let mut tone = config.tone;
+    tone.frequency = audio.frequency.unwrap_or(tone.frequency);
+    tone.volume = audio.volume.unwrap_or(tone.volume);
+    if !timing.is_valid() || !tone.is_valid() {
+        eprintln!("error: invalid speed or tone");
+        return 1;
+    }
 
-    match event {
-        InputEvent::MorseKey(key) => {
-            enigo.key_click(enigo::Key::Backspace); // remove main key
-            match key {
-                MorseKey::Dot => {
-                    unsafe {
-                        spawn(|| Beep(DOT_BEEP_FREQ, DOT_BEEP_DURATION));
-                    }
-                    if state.is_upper_case {
-                        enigo.key_sequence(DOT_KEY_UPPER);
-                    } else {
-                        enigo.key_sequence(DOT_KEY);
-                    }
-                }
-                MorseKey::Dash => {
-                    unsafe {
-                        spawn(|| Beep(DASH_BEEP_FREQ, DASH_BEEP_DURATION));
-                    }
-                    if state.is_upper_case {
-                        enigo.key_sequence(DASH_KEY_UPPER);
-                    } else {
-                        enigo.key_sequence(DASH_KEY);
-                    }
-                }
-            }
+    let segments = audio::keying(&sequences, &timing);
+    let samples = audio::render(&segments, &tone, audio::DEFAULT_SAMPLE_RATE);
+    match audio::write_wav(wav, &samples, audio::DEFAULT_SAMPLE_RATE) {
+        Ok(()) => {
+            println!("{} at {}, {} Hz", wav.display(), timing, tone.frequency);
+            0
         }
-        InputEvent::SequenceParsed(seq, event_key) => {
-            use morse::ConfigKey;
+        Err(err) => {
+            eprintln!("error: failed to write {}: {}", wav.display(), err);
+            1
+        }
+    }
+}
 
-            println!("Sequence parsed: {:?} -> {:?}", seq, event_key);
-            // remove morse keys
-            for _ in 0..seq.len() {
-                enigo.key_click(enigo::Key::Backspace);
+fn decode(cli: &Cli, morse: &str, placeholder: &str) -> i32 {
+    let config = load_config(cli);
+    let Some(lang) = config.start_lang() else {
+        eprintln!("error: no langs defined in config");
+        return 1;
+    };
+    match config.decode_text(lang, morse, placeholder) {
+        Ok(decoded) => {
+            println!("{}", decoded.text);
+            for unknown in &decoded.unknown {
+                eprintln!("warning: {}", unknown);
             }
-            match event_key.key {
-                ConfigKey::Code(code) => {
-                    enigo.key_click(code);
-                }
-                ConfigKey::Layout(layout) => key_click(&mut enigo, layout, event_key.is_upper),
-                ConfigKey::Sequence(seq) => {
-                    for layout in seq {
-                        key_click(&mut enigo, layout, event_key.is_upper);
-                    }
-                }
+            if decoded.unknown.is_empty() {
+                0
+            } else {
+                1
             }
         }
+        Err(err) => {
+            eprintln!("error: {}", err);
+            1
+        }
+    }
+}
+
+fn decode_wav(cli: &Cli, file: &Path, min_frequency: f64, max_frequency: f64) -> i32 {
+    let config = load_config(cli);
+    let (samples, sample_rate) = match cw::read_wav(file) {
+        Ok(wav) => wav,
+        Err(err) => {
+            eprintln!("error: failed to read {}: {}", file.display(), err);
+            return 1;
+        }
+    };
+    let detector = cw::ToneDetector {
+        min_frequency,
+        max_frequency,
+        ..Default::default()
+    };
+    let frequency = detector.detect_frequency(&samples, sample_rate);
+    eprintln!("Tone: {} Hz", frequency);
+    let segments = detector.detect_keying(&samples, sample_rate, frequency);
+    println!("{}", cw::decode_keying(&config, &segments));
+    0
+}
+
+fn replay(cli: &Cli, file: &Path) -> i32 {
+    let config = load_config(cli);
+    match morse::record::read_recording(file) {
+        Ok(keys) => {
+            println!("{}", morse::record::decode_recording(&config, &keys));
+            0
+        }
+        Err(err) => {
+            eprintln!("error: failed to read {}: {}", file.display(), err);
+            1
+        }
+    }
+}
+
+fn list_langs(cli: &Cli) -> i32 {
+    let config = load_config(cli);
+    let start_lang = config.start_lang();
+    let mut langs = config.langs.keys().collect::<Vec<_>>();
+    langs.sort();
+    for lang in langs {
+        if Some(lang) == start_lang {
+            println!("{} (start)", lang);
+        } else {
+            println!("{}", lang);
+        }
+    }
+    0
+}
+
+/// prints config issues, returns exit code
+fn check_config(source: &ConfigSource) -> i32 {
+    let config = match Config::load_from(source) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            return 1;
+        }
+    };
+    println!("Timing: {}", config.timing());
+    let issues = config.validate();
+    for issue in &issues {
+        println!("{}", issue);
+    }
+    if issues.is_empty() {
+        println!("No issues found");
+    }
+    if issues
+        .iter()
+        .any(|issue| issue.severity() == morse::Severity::Error)
+    {
+        1
+    } else {
+        0
+    }
+}
+
+/// key events of OS, polled keys with `--poll-keys` or if events are unavailable
+fn key_source(cli: &Cli, config: &Config) -> io::Result<Box<dyn KeySource>> {
+    if !cli.poll_keys {
+        match morse::system_key_source(config) {
+            Ok(source) => return Ok(Box::new(source)),
+            Err(err) => println!("Key events unavailable, polling keys: {}", err),
+        }
+    }
+    Ok(Box::new(morse::system_polling_key_source(config)?))
+}
+
+/// `--sidetone-wav` file, default output device if `sidetone` feature is enabled
+fn sidetone_sink(cli: &Cli, config: &Config) -> Box<dyn SidetoneSink> {
+    if cli.no_sidetone {
+        return Box::new(NullSidetone);
+    }
+    if let Some(path) = &cli.sidetone_wav {
+        return Box::new(WavSidetone::new(
+            path,
+            config.tone,
+            audio::DEFAULT_SAMPLE_RATE,
+        ));
+    }
+    device_sidetone(config)
+}
+
+#[cfg(feature = "sidetone")]
+fn device_sidetone(config: &Config) -> Box<dyn SidetoneSink> {
+    match morse::sidetone::DeviceSidetone::new(config.tone) {
+        Ok(sidetone) => Box::new(sidetone),
+        Err(err) => {
+            println!("Sidetone unavailable: {}", err);
+            Box::new(NullSidetone)
+        }
+    }
+}
+
+#[cfg(not(feature = "sidetone"))]
+fn device_sidetone(_config: &Config) -> Box<dyn SidetoneSink> {
+    Box::new(NullSidetone)
+}
+
+/// uinput works both on X11 and Wayland, but requires access to /dev/uinput
+#[cfg(target_os = "linux")]
+fn output_sink() -> Box<dyn OutputSink> {
+    match morse::linux::UinputSink::new() {
+        Ok(sink) => Box::new(sink),
+        Err(err) => {
+            println!("Uinput unavailable, using enigo: {}", err);
+            Box::new(morse::output::EnigoSink::new())
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn output_sink() -> Box<dyn OutputSink> {
+    Box::new(morse::output::EnigoSink::new())
+}
+
+fn event_handler(
+    sink: &mut dyn OutputSink,
+    sidetone: &mut dyn SidetoneSink,
+    event: morse::InputEvent,
+    state: &mut morse::InputState,
+) {
+    use morse::InputEvent;
+
+    sink.write_event(&event, state);
+
+    match event {
+        InputEvent::KeyPress(_) | InputEvent::MorseKey(_) => {}
+        InputEvent::KeyingChange(is_down, time) => sidetone.set_keyed(is_down, time),
+        InputEvent::SequenceParsed(seq, event_key) => {
+            println!(
+                "Sequence parsed: {:?} -> {:?} ({:.0} wpm)",
+                seq,
+                event_key,
+                state.press_classifier.estimated_wpm()
+            );
+        }
         InputEvent::SeqRejected(seq, reason) => {
             println!("Sequence rejected: {:?} -> {:?}", seq, reason);
-            // remove morse keys
-            for _ in 0..seq.len() {
-                enigo.key_click(enigo::Key::Backspace);
-            }
+        }
+        InputEvent::WordSeparator(event_key) => {
+            println!("Word separator: {:?}", event_key);
         }
         InputEvent::LangChange(lang) => {
             println!("Lang changed: {:?}", lang);
@@ -108,5 +369,11 @@ fn event_handler(event: morse::InputEvent, state: &mut morse::InputState) {
         InputEvent::Exit => {
             println!("Exit");
         }
+        InputEvent::ConfigReloaded => {
+            println!("Config reloaded");
+        }
+        InputEvent::ConfigReloadFailed(err) => {
+            println!("Config reload failed: {}", err);
+        }
     }
 }
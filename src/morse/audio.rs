@@ -0,0 +1,136 @@
+use super::{MorseKey, MorseSequence, Timing};
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+pub const DEFAULT_SAMPLE_RATE: u32 = 44100;
+
+/// Sine tone of keyed signal.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Tone {
+    /// Hz
+    pub frequency: f64,
+    /// 0 to 1
+    pub volume: f64,
+    /// raised-cosine rise and fall time, removes key clicks
+    pub ramp: Duration,
+}
+
+impl Default for Tone {
+    fn default() -> Self {
+        Tone {
+            frequency: 600.0,
+            volume: 0.5,
+            ramp: Duration::from_millis(5),
+        }
+    }
+}
+
+impl Tone {
+    /// false if frequency is not positive or volume is out of 0 to 1
+    pub fn is_valid(&self) -> bool {
+        self.frequency.is_finite() && self.frequency > 0.0 && (0.0..=1.0).contains(&self.volume)
+    }
+}
+
+/// Part of keyed signal, key is down or up for `duration`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyingSegment {
+    pub is_down: bool,
+    pub duration: Duration,
+}
+
+/// Key down and up segments of sequences by PARIS timing: dot is 1 unit, dash is 3 units,
+/// elements are separated by 1 unit, characters by 3 and words (empty sequences)
+/// by 7 spacing units, see `Timing::spacing_unit`.
+pub fn keying(sequences: &[MorseSequence], timing: &Timing) -> Vec<KeyingSegment> {
+    let unit = timing.unit();
+    let spacing_unit = timing.spacing_unit();
+    let mut segments = Vec::new();
+    let mut push =
+        |is_down: bool, duration: Duration| segments.push(KeyingSegment { is_down, duration });
+
+    let mut is_first = true;
+    let mut is_word_gap = false;
+    for seq in sequences {
+        if seq.is_empty() {
+            is_word_gap = true;
+            continue;
+        }
+        if !is_first {
+            push(false, spacing_unit * if is_word_gap { 7 } else { 3 });
+        }
+        is_first = false;
+        is_word_gap = false;
+
+        for (i, key) in seq.iter().enumerate() {
+            if i > 0 {
+                push(false, unit);
+            }
+            let length = match key {
+                MorseKey::Dot => unit,
+                MorseKey::Dash => unit * 3,
+            };
+            push(true, length);
+        }
+    }
+    segments
+}
+
+/// Mono samples of `tone` keyed by `segments`.
+///
+/// Phase is continuous across segments, each key down segment rises and falls
+/// by half of cosine over `tone.ramp` (shortened to half of segment if it is too long).
+pub fn render(segments: &[KeyingSegment], tone: &Tone, sample_rate: u32) -> Vec<f32> {
+    let sample_rate = sample_rate as f64;
+    let mut samples = Vec::new();
+    let mut sample_index: u64 = 0;
+    for segment in segments {
+        let count = (segment.duration.as_secs_f64() * sample_rate).round() as usize;
+        if !segment.is_down {
+            samples.extend(std::iter::repeat_n(0.0, count));
+            sample_index += count as u64;
+            continue;
+        }
+        let ramp = (tone.ramp.as_secs_f64() * sample_rate).min(count as f64 / 2.0);
+        for i in 0..count {
+            let position = i as f64;
+            let from_edge = position.min(count as f64 - 1.0 - position);
+            let envelope = if from_edge < ramp {
+                0.5 * (1.0 - (PI * from_edge / ramp).cos())
+            } else {
+                1.0
+            };
+            let time = sample_index as f64 / sample_rate;
+            let value = tone.volume * envelope * (2.0 * PI * tone.frequency * time).sin();
+            samples.push(value as f32);
+            sample_index += 1;
+        }
+    }
+    samples
+}
+
+/// Writes 16-bit mono WAV.
+pub fn write_wav(path: impl AsRef<Path>, samples: &[f32], sample_rate: u32) -> io::Result<()> {
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer = hound::WavWriter::create(path, spec).map_err(hound_error)?;
+    for sample in samples {
+        let value = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+        writer.write_sample(value).map_err(hound_error)?;
+    }
+    writer.finalize().map_err(hound_error)
+}
+
+pub(super) fn hound_error(err: hound::Error) -> io::Error {
+    match err {
+        hound::Error::IoError(err) => err,
+        err => io::Error::new(io::ErrorKind::InvalidData, err),
+    }
+}
@@ -0,0 +1,265 @@
+use super::{Config, MorseKey};
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// number of recent presses dot and dash estimates are clustered from
+const WINDOW: usize = 16;
+/// recent presses contain both dots and dashes if longest is this many times the shortest
+const CLUSTER_RATIO: f64 = 2.0;
+/// weight of newest press in running averages, while recent presses are all dots or dashes
+const SMOOTHING: f64 = 0.25;
+/// gaps longer than this many spacing units end word (between 3 and 7)
+const WORD_GAP_UNITS: f64 = 5.0;
+/// presses longer than this many dash estimates are not learned from (e.g. held key)
+const MAX_DASH_RATIO: u32 = 4;
+
+/// Silence between main key presses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Gap {
+    /// between dots and dashes of one character, ~1 dot unit
+    Element,
+    /// ~3 dot units
+    Character,
+    /// ~7 dot units
+    Word,
+}
+
+/// Splits main key presses into dots and dashes.
+///
+/// Estimates dot and dash hold durations by two-cluster averaging of recent presses
+/// and puts threshold between them, so it follows operator speed even if it starts
+/// far from it. While recent presses are all dots or all dashes, estimate of their
+/// class is a running average. Starts with dot of half and dash of one and a half
+/// threshold (1:3 ratio).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PressClassifier {
+    dot: Duration,
+    dash: Duration,
+    /// threshold does not change if true
+    is_locked: bool,
+    /// hold durations of last `WINDOW` presses
+    recent: VecDeque<Duration>,
+    /// dot and gap units of Farnsworth timing, their ratio stretches gaps;
+    /// only explicit `timing` of config stretches them, durations do not
+    timing_units: Option<(Duration, Duration)>,
+}
+
+impl PressClassifier {
+    pub fn new(config: &Config) -> Self {
+        let threshold = config.time_to_long_press;
+        PressClassifier {
+            dot: threshold / 2,
+            dash: threshold * 3 / 2,
+            is_locked: config.lock_long_press,
+            recent: VecDeque::with_capacity(WINDOW),
+            timing_units: config
+                .timing
+                .map(|timing| (timing.unit(), timing.spacing_unit())),
+        }
+    }
+
+    /// Learns from press, then classifies it by updated threshold.
+    /// Zero holds (e.g. of coarse timestamps) are not learned from.
+    pub fn classify(&mut self, hold: Duration) -> MorseKey {
+        if !self.is_locked && !hold.is_zero() && hold <= self.dash * MAX_DASH_RATIO {
+            self.learn(hold);
+        }
+        if hold < self.threshold() {
+            MorseKey::Dot
+        } else {
+            MorseKey::Dash
+        }
+    }
+
+    fn learn(&mut self, hold: Duration) {
+        if self.recent.len() == WINDOW {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(hold);
+        let holds = self
+            .recent
+            .iter()
+            .map(Duration::as_secs_f64)
+            .collect::<Vec<_>>();
+        match two_clusters(&holds, CLUSTER_RATIO) {
+            Some((dot, dash)) => {
+                self.dot = Duration::from_secs_f64(dot);
+                self.dash = Duration::from_secs_f64(dash);
+            }
+            None if hold < self.threshold() => self.dot = average(self.dot, hold),
+            None => self.dash = average(self.dash, hold),
+        }
+    }
+
+    /// shorter presses are dots, others are dashes
+    pub fn threshold(&self) -> Duration {
+        (self.dot + self.dash) / 2
+    }
+
+    pub fn dot(&self) -> Duration {
+        self.dot
+    }
+
+    pub fn dash(&self) -> Duration {
+        self.dash
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.is_locked
+    }
+
+    /// dot unit averaged from dot and a third of dash estimates
+    pub fn unit(&self) -> Duration {
+        (self.dot + self.dash / 3) / 2
+    }
+
+    /// Words per minute by PARIS standard (dot of `1200 / wpm` ms).
+    pub fn estimated_wpm(&self) -> f64 {
+        let unit = self.unit().as_secs_f64();
+        if unit > 0.0 {
+            1.2 / unit
+        } else {
+            0.0
+        }
+    }
+
+    /// gap unit, stretched by Farnsworth timing
+    pub fn spacing_unit(&self) -> Duration {
+        match self.timing_units {
+            Some((unit, spacing_unit)) if !unit.is_zero() => self
+                .unit()
+                .mul_f64(spacing_unit.as_secs_f64() / unit.as_secs_f64()),
+            _ => self.unit(),
+        }
+    }
+
+    /// gap after which pending character is complete,
+    /// between element gap (1 unit) and character gap (3 spacing units)
+    pub fn character_gap(&self) -> Duration {
+        (self.unit() + self.spacing_unit() * 3) / 2
+    }
+
+    /// gap after which word is complete
+    pub fn word_gap(&self) -> Duration {
+        self.spacing_unit().mul_f64(WORD_GAP_UNITS)
+    }
+
+    pub fn classify_gap(&self, gap: Duration) -> Gap {
+        if gap > self.word_gap() {
+            Gap::Word
+        } else if gap > self.character_gap() {
+            Gap::Character
+        } else {
+            Gap::Element
+        }
+    }
+}
+
+/// Means of short and long values by two-cluster averaging,
+/// none if longest value is not `ratio` times longer than shortest one or is not positive.
+pub(crate) fn two_clusters(values: &[f64], ratio: f64) -> Option<(f64, f64)> {
+    let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = values.iter().copied().fold(0.0, f64::max);
+    if max <= 0.0 || max < min * ratio {
+        return None;
+    }
+    let (mut short, mut long) = (min, max);
+    for _ in 0..10 {
+        let threshold = (short + long) / 2.0;
+        let (shorts, longs): (Vec<f64>, Vec<f64>) =
+            values.iter().partition(|&&value| value < threshold);
+        if shorts.is_empty() || longs.is_empty() {
+            return None;
+        }
+        short = shorts.iter().sum::<f64>() / shorts.len() as f64;
+        long = longs.iter().sum::<f64>() / longs.len() as f64;
+    }
+    Some((short, long))
+}
+
+fn average(estimate: Duration, sample: Duration) -> Duration {
+    estimate.mul_f64(1.0 - SMOOTHING) + sample.mul_f64(SMOOTHING)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::two_clusters;
+    use crate::morse::audio::keying;
+    use crate::morse::decoder::decode_inputs;
+    use crate::morse::{Config, DecoderInput, KeyerMode, Timing};
+    use std::time::{Duration, Instant};
+
+    /// straight key timeline of `text` sent by `timing`
+    fn straight_key_inputs(config: &Config, text: &str, timing: Timing) -> Vec<DecoderInput> {
+        let sequences = config.encode("en", text).unwrap();
+        let mut time = Instant::now();
+        let mut inputs = Vec::new();
+        for segment in keying(&sequences, &timing) {
+            inputs.push(if segment.is_down {
+                DecoderInput::KeyDown(config.main, time)
+            } else {
+                DecoderInput::KeyUp(config.main, time)
+            });
+            time += segment.duration;
+        }
+        inputs.push(DecoderInput::KeyUp(config.main, time));
+        inputs
+    }
+
+    /// starts from built-in 100 ms threshold, gaps are not stretched
+    fn test_config() -> Config {
+        let mut config = Config::default_config();
+        config.lang = Some("en".into());
+        config.keyer = KeyerMode::Straight;
+        config.timing = Some(Timing::new(12.0));
+        config.accept_sequence_delay = Duration::from_secs(1);
+        config
+    }
+
+    #[test]
+    fn learns_operator_faster_than_threshold() {
+        // 30 ms unit, dashes of 90 ms are shorter than threshold
+        let config = test_config();
+        let inputs = straight_key_inputs(&config, "paris paris", Timing::new(40.0));
+        assert_eq!(decode_inputs(config, inputs), "paris paris");
+    }
+
+    #[test]
+    fn learns_operator_slower_than_threshold() {
+        // 150 ms unit, dots are longer than threshold,
+        // so nothing tells first dot from dash until first word is sent
+        let config = test_config();
+        let inputs = straight_key_inputs(&config, "paris paris paris", Timing::new(8.0));
+        let text = decode_inputs(config, inputs);
+        assert!(text.ends_with(" paris paris"), "{:?}", text);
+    }
+
+    #[test]
+    fn locked_threshold_does_not_learn() {
+        let mut config = test_config();
+        config.lock_long_press = true;
+        let inputs = straight_key_inputs(&config, "e e", Timing::new(8.0));
+        assert_eq!(decode_inputs(config, inputs), "t t");
+    }
+
+    #[test]
+    fn zero_holds_are_not_learned() {
+        let config = test_config();
+        let start = Instant::now();
+        let mut inputs = Vec::new();
+        // press and release at same instant, then dot of 30 ms
+        for (down, up) in [(0, 0), (1000, 1030)] {
+            let at = |ms| start + Duration::from_millis(ms);
+            inputs.push(DecoderInput::KeyDown(config.main, at(down)));
+            inputs.push(DecoderInput::KeyUp(config.main, at(up)));
+        }
+        assert_eq!(decode_inputs(config, inputs), "e e");
+    }
+
+    #[test]
+    fn two_clusters_of_zeros_are_none() {
+        assert_eq!(two_clusters(&[0.0, 0.0], 2.0), None);
+        assert_eq!(two_clusters(&[], 2.0), None);
+        assert_eq!(two_clusters(&[0.0, 0.1, 0.3], 2.0), Some((0.05, 0.3)));
+    }
+}
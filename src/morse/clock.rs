@@ -0,0 +1,106 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Monotonic time of key events and ticks.
+///
+/// Wall-clock time may step back (NTP, manual changes), monotonic one never does,
+/// so hold durations and gaps can not go negative or absurd.
+pub trait Clock {
+    fn now(&self) -> Instant;
+}
+
+/// `Instant::now`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// Clock that moves only by `advance`, clones share time.
+#[derive(Debug, Clone)]
+pub struct FakeClock {
+    now: Arc<Mutex<Instant>>,
+}
+
+impl FakeClock {
+    pub fn new(start: Instant) -> Self {
+        FakeClock {
+            now: Arc::new(Mutex::new(start)),
+        }
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        let mut now = self.now.lock().unwrap_or_else(|err| err.into_inner());
+        *now += duration;
+    }
+}
+
+impl Default for FakeClock {
+    fn default() -> Self {
+        FakeClock::new(Instant::now())
+    }
+}
+
+impl Clock for FakeClock {
+    fn now(&self) -> Instant {
+        *self.now.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for &C {
+    fn now(&self) -> Instant {
+        (**self).now()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Clock, FakeClock};
+    use crate::morse::output::config_key_text;
+    use crate::morse::{listen_loop, ChannelKeySource, Config, InputEvent, KeyCode, KeyEvent};
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    #[test]
+    fn listen_loop_ticks_by_fake_clock() {
+        let mut config = Config::default_config();
+        config.lang = Some("en".into());
+        let clock = FakeClock::default();
+        let (sender, receiver) = mpsc::channel();
+        let mut source = ChannelKeySource::new(receiver);
+        let send = |key: KeyCode, is_down: bool| {
+            let time = clock.now();
+            sender.send(KeyEvent { key, is_down, time }).unwrap();
+        };
+
+        // dot of 30 ms
+        send(config.main, true);
+        clock.advance(Duration::from_millis(30));
+        send(config.main, false);
+
+        let mut events = Vec::new();
+        let result = listen_loop(&config, None, &mut source, &clock, |event, _| {
+            match &event {
+                // sequence is accepted only once fake time passes
+                InputEvent::MorseKey(_) => clock.advance(Duration::from_secs(1)),
+                InputEvent::SequenceParsed(..) => {
+                    send(config.exit, true);
+                    send(config.exit, false);
+                }
+                _ => {}
+            }
+            events.push(event);
+        });
+
+        assert!(result.is_ok());
+        let parsed = events.iter().find_map(|event| match event {
+            InputEvent::SequenceParsed(_, event_key) => Some(config_key_text(&event_key.key)),
+            _ => None,
+        });
+        assert_eq!(parsed.as_deref(), Some("e"));
+        assert_eq!(events.last(), Some(&InputEvent::Exit));
+    }
+}
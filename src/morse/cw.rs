@@ -0,0 +1,287 @@
+use super::audio::{hound_error, KeyingSegment};
+use super::classifier::two_clusters;
+use super::decoder::decode_inputs;
+use super::{Config, DecoderInput, KeyerMode, Timing};
+use std::f64::consts::PI;
+use std::io;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Tone detection settings.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ToneDetector {
+    /// band where tone frequency is searched, Hz
+    pub min_frequency: f64,
+    pub max_frequency: f64,
+    /// frequency search step, Hz
+    pub frequency_step: f64,
+    /// length of blocks tone is detected in, limits timing resolution
+    pub block: Duration,
+}
+
+impl Default for ToneDetector {
+    fn default() -> Self {
+        ToneDetector {
+            min_frequency: 300.0,
+            max_frequency: 1200.0,
+            frequency_step: 10.0,
+            block: Duration::from_millis(5),
+        }
+    }
+}
+
+/// Mono samples of WAV file with its sample rate, channels are mixed.
+pub fn read_wav(path: impl AsRef<Path>) -> io::Result<(Vec<f32>, u32)> {
+    let reader = hound::WavReader::open(path).map_err(hound_error)?;
+    let spec = reader.spec();
+    let samples = match spec.sample_format {
+        hound::SampleFormat::Float => reader
+            .into_samples::<f32>()
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(hound_error)?,
+        hound::SampleFormat::Int => {
+            let scale = (1i64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .into_samples::<i32>()
+                .map(|sample| sample.map(|sample| sample as f32 / scale))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(hound_error)?
+        }
+    };
+    let channels = spec.channels.max(1) as usize;
+    let mono = samples
+        .chunks(channels)
+        .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+        .collect();
+    Ok((mono, spec.sample_rate))
+}
+
+/// Power of `frequency` in `samples` by Goertzel algorithm.
+pub fn goertzel(samples: &[f32], sample_rate: u32, frequency: f64) -> f64 {
+    let coefficient = 2.0 * (2.0 * PI * frequency / sample_rate as f64).cos();
+    let (mut previous, mut before_previous) = (0.0, 0.0);
+    for &sample in samples {
+        let current = sample as f64 + coefficient * previous - before_previous;
+        before_previous = previous;
+        previous = current;
+    }
+    previous * previous + before_previous * before_previous
+        - coefficient * previous * before_previous
+}
+
+impl ToneDetector {
+    fn block_len(&self, sample_rate: u32) -> usize {
+        ((self.block.as_secs_f64() * sample_rate as f64).round() as usize).max(1)
+    }
+
+    /// Frequency of band with most power, summed over blocks so tone does not
+    /// have to be continuous.
+    pub fn detect_frequency(&self, samples: &[f32], sample_rate: u32) -> f64 {
+        // longer blocks for finer frequency resolution than keying needs
+        let block_len = self.block_len(sample_rate) * 4;
+        let mut best = (self.min_frequency, f64::MIN);
+        let mut frequency = self.min_frequency;
+        while frequency <= self.max_frequency {
+            let power = samples
+                .chunks(block_len)
+                .map(|block| goertzel(block, sample_rate, frequency))
+                .sum::<f64>();
+            if power > best.1 {
+                best = (frequency, power);
+            }
+            frequency += self.frequency_step;
+        }
+        best.0
+    }
+
+    /// Key down and up segments of tone at `frequency`.
+    ///
+    /// Block magnitudes are split between noise floor (low percentile) and signal level
+    /// (maximum) with hysteresis, so noise near threshold does not toggle key.
+    pub fn detect_keying(
+        &self,
+        samples: &[f32],
+        sample_rate: u32,
+        frequency: f64,
+    ) -> Vec<KeyingSegment> {
+        let block_len = self.block_len(sample_rate);
+        let block = Duration::from_secs_f64(block_len as f64 / sample_rate as f64);
+        let magnitudes = samples
+            .chunks(block_len)
+            .map(|block| goertzel(block, sample_rate, frequency).sqrt())
+            .collect::<Vec<_>>();
+        if magnitudes.is_empty() {
+            return Vec::new();
+        }
+
+        let mut sorted = magnitudes.clone();
+        sorted.sort_by(f64::total_cmp);
+        let percentile = |p: f64| sorted[((sorted.len() - 1) as f64 * p) as usize];
+        let (low, high) = (percentile(0.1), percentile(1.0));
+        let (on, off) = (low + (high - low) * 0.6, low + (high - low) * 0.4);
+
+        let mut segments: Vec<KeyingSegment> = Vec::new();
+        let mut is_down = false;
+        for magnitude in magnitudes {
+            if high - low > f64::EPSILON {
+                if !is_down && magnitude > on {
+                    is_down = true;
+                } else if is_down && magnitude < off {
+                    is_down = false;
+                }
+            }
+            match segments.last_mut() {
+                Some(last) if last.is_down == is_down => last.duration += block,
+                _ => segments.push(KeyingSegment {
+                    is_down,
+                    duration: block,
+                }),
+            }
+        }
+        segments
+    }
+}
+
+/// Speed of keyed segments: dot unit from clusters of key down durations (dots and dashes),
+/// Farnsworth speed from character gaps if they are stretched.
+///
+/// Keying without both dots and dashes or with gaps of one length is ambiguous, it is taken
+/// as sent by `expected` timing where it fits: key downs shorter than its threshold are dots,
+/// gaps close to its (stretched) character gap are character gaps. None if nothing is keyed.
+pub fn estimate_timing(segments: &[KeyingSegment], expected: &Timing) -> Option<Timing> {
+    let durations = |is_down: bool| {
+        segments
+            .iter()
+            .filter(|segment| segment.is_down == is_down)
+            .map(|segment| segment.duration.as_secs_f64())
+            .collect::<Vec<_>>()
+    };
+    let key_downs = durations(true);
+    // dashes are 3 dots long
+    let unit = match two_clusters(&key_downs, 2.0) {
+        Some((dot, dash)) => (dot + dash / 3.0) / 2.0,
+        None if !key_downs.is_empty() => {
+            let key_down = key_downs.iter().sum::<f64>() / key_downs.len() as f64;
+            match key_down < expected.time_to_long_press().as_secs_f64() {
+                true => key_down,
+                false => key_down / 3.0,
+            }
+        }
+        None => return None,
+    };
+    let mut timing = Timing::new(Timing::new(1.0).unit().as_secs_f64() / unit);
+    let expected_character_gap = unit * expected.spacing_ratio() * 3.0;
+
+    // character gaps are 3 and word gaps are 7 spacing units, element gaps are skipped
+    let gaps = durations(false)
+        .into_iter()
+        .filter(|&gap| gap > unit * 2.0)
+        .collect::<Vec<_>>();
+    let character_gap = match two_clusters(&gaps, 1.8) {
+        Some((character_gap, _)) => Some(character_gap),
+        // only character gaps if they are shorter than 7 units or match expected ones
+        None if !gaps.is_empty() => {
+            Some(gaps.iter().sum::<f64>() / gaps.len() as f64).filter(|&gap| {
+                gap < unit * 5.0
+                    || (expected_character_gap * 0.7..expected_character_gap * 1.4).contains(&gap)
+            })
+        }
+        None => None,
+    };
+    if let Some(character_gap) = character_gap.filter(|&gap| gap > unit * 3.5) {
+        let stretched = Timing::from_durations(
+            timing.time_to_long_press(),
+            Duration::from_secs_f64((unit + character_gap) / 2.0),
+        );
+        timing.farnsworth_wpm = stretched.farnsworth_wpm;
+    }
+    Some(timing)
+}
+
+/// Decodes keyed segments with straight key `main` of `config`, text is written as by text sinks.
+///
+/// Timing starts at one estimated from segments, then adapts as usual.
+pub fn decode_keying(config: &Config, segments: &[KeyingSegment]) -> String {
+    let mut config = config.clone();
+    config.keyer = KeyerMode::Straight;
+    if let Some(timing) = estimate_timing(segments, &config.timing()) {
+        config.timing = Some(timing);
+        config.time_to_long_press = timing.time_to_long_press();
+        config.accept_sequence_delay = timing.accept_sequence_delay();
+    }
+    let main = config.main;
+    let mut time = Instant::now();
+    let mut was_down = false;
+    let mut inputs = segments
+        .iter()
+        .flat_map(|segment| {
+            let input = match (was_down, segment.is_down) {
+                (false, true) => Some(DecoderInput::KeyDown(main, time)),
+                (true, false) => Some(DecoderInput::KeyUp(main, time)),
+                _ => None,
+            };
+            was_down = segment.is_down;
+            time += segment.duration;
+            input
+        })
+        .collect::<Vec<_>>();
+    // release key after last segment
+    inputs.push(DecoderInput::KeyUp(main, time));
+    decode_inputs(config, inputs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_keying, ToneDetector};
+    use crate::morse::audio::{keying, render};
+    use crate::morse::{Config, Timing, Tone};
+
+    /// text keyed by `timing`, rendered as tone and decoded back from samples
+    fn round_trip(text: &str, timing: Timing) -> String {
+        let mut config = Config::default_config();
+        config.lang = Some("en".into());
+        let sequences = config.encode("en", text).unwrap();
+        let tone = Tone::default();
+        let sample_rate = 8000;
+        let samples = render(&keying(&sequences, &timing), &tone, sample_rate);
+
+        let detector = ToneDetector::default();
+        let frequency = detector.detect_frequency(&samples, sample_rate);
+        assert!(
+            (frequency - tone.frequency).abs() <= detector.frequency_step,
+            "{} Hz",
+            frequency
+        );
+        let segments = detector.detect_keying(&samples, sample_rate, frequency);
+        decode_keying(&config, &segments)
+    }
+
+    #[test]
+    fn rendered_keying_decodes_back() {
+        for wpm in [15.0, 20.0, 30.0] {
+            assert_eq!(
+                round_trip("hello, world", Timing::new(wpm)),
+                "hello, world",
+                "{} wpm",
+                wpm
+            );
+        }
+    }
+
+    #[test]
+    fn rendered_farnsworth_keying_decodes_back() {
+        for (wpm, farnsworth_wpm) in [(18.0, 10.0), (20.0, 5.0), (25.0, 15.0)] {
+            let timing = Timing {
+                wpm,
+                farnsworth_wpm: Some(farnsworth_wpm),
+            };
+            assert_eq!(
+                round_trip("hello, world", timing),
+                "hello, world",
+                "{} wpm (Farnsworth {} wpm)",
+                wpm,
+                farnsworth_wpm
+            );
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use super::output::config_key_text;
+use super::{morse_seq_from_string, Config};
+use std::fmt;
+
+/// Text decoded by `Config::decode_text`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DecodedText {
+    pub text: String,
+    /// sequences replaced with placeholder, in order of appearance
+    pub unknown: Vec<UnknownSequence>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownSequence {
+    /// position (in characters) of sequence in decoded input
+    pub position: usize,
+    pub sequence: String,
+}
+
+impl fmt::Display for UnknownSequence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "unknown sequence {:?} at {}",
+            self.sequence, self.position
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    UnknownLang(String),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::UnknownLang(lang) => write!(f, "lang {:?} is not defined", lang),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+impl Config {
+    /// Decodes dots and dashes like `encode::format_sequences` output: sequences are separated
+    /// by whitespace, words by `/`, which become `word_separator`.
+    ///
+    /// Sequences are looked up like in `listen_loop` (see `Config::lookup`), unknown ones
+    /// (including ones with characters other than `.` and `-`) are replaced with `placeholder`.
+    pub fn decode_text(
+        &self,
+        lang: &str,
+        morse: &str,
+        placeholder: &str,
+    ) -> Result<DecodedText, DecodeError> {
+        if !self.langs.contains_key(lang) {
+            return Err(DecodeError::UnknownLang(lang.to_string()));
+        }
+        let word_separator = config_key_text(&self.word_separator);
+
+        let mut decoded = DecodedText::default();
+        // text ends with word separator, so repeated `/` produce one
+        let mut is_word_end = true;
+        for (position, token) in tokens(morse) {
+            if token == "/" {
+                if !is_word_end {
+                    decoded.text.push_str(&word_separator);
+                    is_word_end = true;
+                }
+                continue;
+            }
+            let key = morse_seq_from_string(token)
+                .ok()
+                .and_then(|seq| self.lookup(Some(lang), &seq));
+            match key {
+                Some(key) => decoded.text.push_str(&config_key_text(key)),
+                None => {
+                    decoded.text.push_str(placeholder);
+                    decoded.unknown.push(UnknownSequence {
+                        position,
+                        sequence: token.to_string(),
+                    });
+                }
+            }
+            is_word_end = false;
+        }
+        if is_word_end && decoded.text.ends_with(&word_separator) {
+            decoded
+                .text
+                .truncate(decoded.text.len() - word_separator.len());
+        }
+        Ok(decoded)
+    }
+}
+
+/// whitespace separated tokens with their positions in characters, `/` is a token by itself
+fn tokens(morse: &str) -> Vec<(usize, &str)> {
+    let mut tokens = Vec::new();
+    let mut start: Option<(usize, usize)> = None;
+    for (position, (offset, character)) in morse.char_indices().enumerate() {
+        if character.is_whitespace() || character == '/' {
+            if let Some((start_position, start_offset)) = start.take() {
+                tokens.push((start_position, &morse[start_offset..offset]));
+            }
+            if character == '/' {
+                tokens.push((position, "/"));
+            }
+        } else if start.is_none() {
+            start = Some((position, offset));
+        }
+    }
+    if let Some((start_position, start_offset)) = start {
+        tokens.push((start_position, &morse[start_offset..]));
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DecodeError, UnknownSequence};
+    use crate::morse::{morse_seq_from_string, Config, ConfigKey, ConfigLayoutKey, KeyCode};
+
+    fn unknown(position: usize, sequence: &str) -> UnknownSequence {
+        UnknownSequence {
+            position,
+            sequence: sequence.to_string(),
+        }
+    }
+
+    #[test]
+    fn unknown_and_invalid_sequences_are_placeholders() {
+        let config = Config::default_config();
+        let decoded = config
+            .decode_text("en", "... ........ ---  x-", "<?>")
+            .unwrap();
+        assert_eq!(decoded.text, "s<?>o<?>");
+        assert_eq!(decoded.unknown, [unknown(4, "........"), unknown(18, "x-")]);
+        assert_eq!(
+            decoded.unknown[1].to_string(),
+            "unknown sequence \"x-\" at 18"
+        );
+    }
+
+    #[test]
+    fn slashes_separate_words_once() {
+        let config = Config::default_config();
+        let decode = |morse| config.decode_text("en", morse, "?").unwrap().text;
+        assert_eq!(decode(". / -"), "e t");
+        assert_eq!(decode("./-"), "e t");
+        assert_eq!(decode("/ / . // / - / /"), "e t");
+        assert_eq!(decode(""), "");
+        assert_eq!(
+            config.decode_text("de", ".", "?"),
+            Err(DecodeError::UnknownLang("de".into()))
+        );
+    }
+
+    #[test]
+    fn functional_sequences_are_looked_up_before_lang() {
+        let mut config = Config::default_config();
+        // "......" is functional "."
+        let seq = morse_seq_from_string("......").unwrap();
+        let key = ConfigKey::Layout(ConfigLayoutKey {
+            lower: KeyCode::Layout('~'),
+        });
+        config.langs.get_mut("en").unwrap().insert(seq, key);
+        let decoded = config.decode_text("en", "...... .", "?").unwrap();
+        assert_eq!(decoded.text, ".e");
+    }
+}
@@ -0,0 +1,775 @@
+use super::output::TextBuffer;
+use super::{
+    Config, Gap, InputEvent, InputEventKey, InputState, InputWorkState, KeyCode, KeyEvent,
+    KeyState, Keyer, KeyerElement, OutputSink, Paddle, PressClassifier, SequenceRejectReason,
+};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecoderInput {
+    KeyDown(KeyCode, Instant),
+    KeyUp(KeyCode, Instant),
+    /// no key changes until this time, pending sequence is accepted on ticks
+    Tick(Instant),
+}
+
+impl DecoderInput {
+    pub fn time(&self) -> Instant {
+        match *self {
+            DecoderInput::KeyDown(_, time)
+            | DecoderInput::KeyUp(_, time)
+            | DecoderInput::Tick(time) => time,
+        }
+    }
+}
+
+impl From<KeyEvent> for DecoderInput {
+    fn from(event: KeyEvent) -> Self {
+        if event.is_down {
+            DecoderInput::KeyDown(event.key, event.time)
+        } else {
+            DecoderInput::KeyUp(event.key, event.time)
+        }
+    }
+}
+
+/// Morse decoding state machine.
+///
+/// Does not read clocks or keys by itself: all time is taken from inputs,
+/// so same inputs always produce same events.
+/// Paddle keyers are driven by same inputs, their elements are produced on ticks.
+#[derive(Debug, Clone)]
+pub struct Decoder {
+    config: Config,
+    state: InputState,
+    /// none for straight key
+    keyer: Option<Keyer>,
+}
+
+impl Decoder {
+    pub fn new(config: Config) -> Self {
+        let state = InputState::new(&config);
+        let keyer = new_keyer(&config);
+        Decoder {
+            config,
+            state,
+            keyer,
+        }
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn state(&self) -> &InputState {
+        &self.state
+    }
+
+    pub fn state_mut(&mut self) -> &mut InputState {
+        &mut self.state
+    }
+
+    pub fn keyer(&self) -> Option<&Keyer> {
+        self.keyer.as_ref()
+    }
+
+    /// Nothing is pending: ticks change nothing until next key input.
+    pub fn is_idle(&self) -> bool {
+        self.state.sequence.is_empty()
+            && self.state.last_main_key_press.is_none()
+            && self.keyer.as_ref().is_none_or(Keyer::is_idle)
+    }
+
+    pub fn estimated_wpm(&self) -> f64 {
+        self.state.press_classifier.estimated_wpm()
+    }
+
+    /// Swaps config keeping case, pause and pending sequence.
+    /// Current lang is kept if new config still has it, start lang is used otherwise.
+    /// Learned dot and dash durations are kept unless long press settings change.
+    /// Replaced keyer sends its elements complete until `time` and releases keying,
+    /// paddles held at `time` stay held in new one.
+    pub fn replace_config(&mut self, config: Config, time: Instant) -> Vec<InputEvent> {
+        let mut events = Vec::new();
+        if config.time_to_long_press != self.config.time_to_long_press
+            || config.lock_long_press != self.config.lock_long_press
+            || config.timing != self.config.timing
+        {
+            self.state.press_classifier = PressClassifier::new(&config);
+        }
+        if config.keyer != self.config.keyer
+            || config.dit_memory != self.config.dit_memory
+            || config.dah_memory != self.config.dah_memory
+            || config.timing() != self.config.timing()
+        {
+            self.replace_keyer(&config, time, &mut events);
+        }
+        let is_lang_kept = self
+            .state
+            .lang
+            .as_ref()
+            .is_some_and(|lang| config.langs.contains_key(lang));
+        if !is_lang_kept {
+            self.state.lang = config.start_lang().cloned();
+        }
+        self.config = config;
+        events
+    }
+
+    fn replace_keyer(&mut self, config: &Config, time: Instant, events: &mut Vec<InputEvent>) {
+        let mut held_paddles = Vec::new();
+        if let Some(keyer) = &mut self.keyer {
+            let elements = keyer.tick(time);
+            held_paddles = [Paddle::Dit, Paddle::Dah]
+                .into_iter()
+                .filter(|&paddle| keyer.is_pressed(paddle))
+                .collect();
+            self.send_elements(elements, events);
+        }
+        // press of old manual key is not released by new one
+        if config.manual_key() != self.config.manual_key() {
+            self.state.main_key_state = KeyState::NotPressed;
+        }
+        if self.state.main_key_state == KeyState::NotPressed {
+            self.set_keyed(false, time, events);
+        }
+
+        self.keyer = new_keyer(config);
+        if let Some(keyer) = &mut self.keyer {
+            for paddle in held_paddles {
+                let is_same_key = match paddle {
+                    Paddle::Dit => config.dit == self.config.dit,
+                    Paddle::Dah => config.dah == self.config.dah,
+                };
+                if is_same_key {
+                    keyer.press(paddle, true, time);
+                }
+            }
+        }
+    }
+
+    /// inputs must come in time order, nothing is produced after exit
+    pub fn handle(&mut self, input: DecoderInput) -> Vec<InputEvent> {
+        let mut events = Vec::new();
+        if self.state.work_state == InputWorkState::Exit {
+            return events;
+        }
+        if let DecoderInput::KeyDown(key, _) = input {
+            if self.is_keying_press(key) {
+                events.push(InputEvent::KeyPress(key));
+            }
+        }
+
+        // keyer elements and sequence may be complete before this input
+        // if there were no ticks in between
+        let time = input.time();
+        if let Some(keyer) = &mut self.keyer {
+            let elements = keyer.tick(time);
+            self.send_elements(elements, &mut events);
+        }
+        self.accept_sequence(self.gap_end(time), &mut events);
+
+        match input {
+            DecoderInput::KeyDown(key, time) => self.handle_key(
+                KeyEvent {
+                    key,
+                    is_down: true,
+                    time,
+                },
+                &mut events,
+            ),
+            DecoderInput::KeyUp(key, time) => self.handle_key(
+                KeyEvent {
+                    key,
+                    is_down: false,
+                    time,
+                },
+                &mut events,
+            ),
+            DecoderInput::Tick(_) => {}
+        }
+
+        // keyer element in progress is keyed before it is complete
+        if let Some(start) = self.keyer.as_ref().and_then(Keyer::pending_element_start) {
+            self.set_keyed(true, start, &mut events);
+        }
+
+        events
+    }
+
+    fn handle_key(&mut self, key_event: KeyEvent, events: &mut Vec<InputEvent>) {
+        // handle main key (or manual contact of bug) press
+        let is_manual_key = Some(key_event.key) == self.config.manual_key();
+        if is_manual_key
+            && matches!(self.state.main_key_state, KeyState::NotPressed) == key_event.is_down
+        {
+            self.set_keyed(key_event.is_down, key_event.time, events);
+        }
+        let state = &mut self.state;
+        if is_manual_key {
+            if let Some(main_key_hold_duration) =
+                update_key_state(&mut state.main_key_state, &key_event)
+            {
+                if state.work_state == InputWorkState::Work {
+                    let morse_key = state.press_classifier.classify(main_key_hold_duration);
+                    state.sequence.push(morse_key);
+                    state.last_main_key_press = Some(key_event.time);
+
+                    events.push(InputEvent::MorseKey(morse_key));
+                }
+            }
+        }
+
+        // handle paddles
+        let config = &self.config;
+        let paddle = match config.keyer.is_paddle() {
+            true if Some(key_event.key) == config.dit => Some(Paddle::Dit),
+            true if Some(key_event.key) == config.dah => Some(Paddle::Dah),
+            _ => None,
+        };
+        if let (Some(paddle), Some(keyer)) = (paddle, &mut self.keyer) {
+            let elements = keyer.press(paddle, key_event.is_down, key_event.time);
+            self.send_elements(elements, events);
+        }
+        let config = &self.config;
+        let state = &mut self.state;
+
+        // handle other keys
+        // if lang is None, then config not contains any lang
+        if key_event.key == config.change_lang
+            && update_key_state(&mut state.lang_key_state, &key_event).is_some()
+            && state.work_state == InputWorkState::Work
+            && state.lang.is_some()
+        {
+            // cyclically find next lang in sorted config.langs
+            let curr_lang = state.lang.as_ref().unwrap().clone();
+            let mut keys = config.langs.keys().collect::<Vec<_>>();
+            keys.sort();
+            let curr_lang_iter = keys
+                .iter()
+                .position(|s| *s == &curr_lang)
+                .expect("current lang not found in config.langs");
+            let next_lang = keys[(curr_lang_iter + 1) % keys.len()].clone();
+            state.lang = Some(next_lang.clone());
+
+            events.push(InputEvent::LangChange(next_lang));
+        }
+
+        if key_event.key == config.change_case
+            && update_key_state(&mut state.change_case_key_state, &key_event).is_some()
+            && state.work_state == InputWorkState::Work
+        {
+            state.is_upper_case = !state.is_upper_case;
+
+            events.push(InputEvent::CaseChange(state.is_upper_case));
+        }
+
+        if key_event.key == config.pause
+            && update_key_state(&mut state.pause_key_state, &key_event).is_some()
+            && state.work_state != InputWorkState::Exit
+        {
+            state.work_state = match state.work_state {
+                InputWorkState::Pause => InputWorkState::Work,
+                InputWorkState::Work => InputWorkState::Pause,
+                InputWorkState::Exit => InputWorkState::Exit,
+            };
+
+            events.push(InputEvent::PauseToggle(
+                state.work_state == InputWorkState::Pause,
+            ));
+        }
+
+        if key_event.key == config.exit
+            && update_key_state(&mut state.exit_key_state, &key_event).is_some()
+        {
+            state.work_state = InputWorkState::Exit;
+
+            events.push(InputEvent::Exit);
+        }
+    }
+
+    /// true if `key` goes down and it is manual key or paddle that is up, while working
+    fn is_keying_press(&self, key: KeyCode) -> bool {
+        let config = &self.config;
+        if self.state.work_state != InputWorkState::Work {
+            return false;
+        }
+        if Some(key) == config.manual_key() {
+            return self.state.main_key_state == KeyState::NotPressed;
+        }
+        let paddle = match config.keyer.is_paddle() {
+            true if Some(key) == config.dit => Paddle::Dit,
+            true if Some(key) == config.dah => Paddle::Dah,
+            _ => return false,
+        };
+        self.keyer
+            .as_ref()
+            .is_some_and(|keyer| !keyer.is_pressed(paddle))
+    }
+
+    /// Pushes keyer elements as morse keys, sequence before each of them may be complete.
+    fn send_elements(&mut self, elements: Vec<KeyerElement>, events: &mut Vec<InputEvent>) {
+        for element in elements {
+            self.accept_sequence(element.start, events);
+            self.set_keyed(true, element.start, events);
+            self.set_keyed(false, element.end, events);
+            if self.state.work_state == InputWorkState::Work {
+                self.state.sequence.push(element.key);
+                self.state.last_main_key_press = Some(element.end);
+                events.push(InputEvent::MorseKey(element.key));
+            }
+        }
+    }
+
+    /// Reports keying change, key does not go down while paused but always goes up.
+    fn set_keyed(&mut self, is_down: bool, time: Instant, events: &mut Vec<InputEvent>) {
+        let state = &mut self.state;
+        if state.is_keyed == is_down || (is_down && state.work_state != InputWorkState::Work) {
+            return;
+        }
+        state.is_keyed = is_down;
+        events.push(InputEvent::KeyingChange(is_down, time));
+    }
+
+    /// Silence ends at `time`, or earlier if keyer key is down since then.
+    fn gap_end(&self, time: Instant) -> Instant {
+        self.keyer
+            .as_ref()
+            .and_then(Keyer::pending_element_start)
+            .map_or(time, |start| start.min(time))
+    }
+
+    /// Sequence is complete after character gap (capped by `accept_sequence_delay`),
+    /// word separator follows parsed sequence after word gap.
+    /// Gaps are counted from last main key release while work_state is work and main key is up.
+    fn accept_sequence(&mut self, time: Instant, events: &mut Vec<InputEvent>) {
+        let state = &self.state;
+        if state.work_state != InputWorkState::Work || state.main_key_state != KeyState::NotPressed
+        {
+            return;
+        }
+        let Some(last_main_key_press) = state.last_main_key_press else {
+            return;
+        };
+        let gap = time.saturating_duration_since(last_main_key_press);
+        let classifier = &state.press_classifier;
+        let character_gap = classifier
+            .character_gap()
+            .min(self.config.accept_sequence_delay);
+
+        if !state.sequence.is_empty() && gap > character_gap {
+            let event = self.parse_sequence();
+            // no word separator after rejected sequence or separator itself
+            let is_word_pending = matches!(
+                &event,
+                InputEvent::SequenceParsed(_, event_key) if event_key.key != self.config.word_separator
+            );
+            events.push(event);
+            self.state.sequence.clear();
+            if !is_word_pending {
+                self.state.last_main_key_press = None;
+                return;
+            }
+        }
+
+        let state = &self.state;
+        if state.sequence.is_empty() && state.press_classifier.classify_gap(gap) == Gap::Word {
+            events.push(InputEvent::WordSeparator(InputEventKey {
+                key: self.config.word_separator.clone(),
+                is_upper: state.is_upper_case,
+            }));
+            self.state.last_main_key_press = None;
+        }
+    }
+
+    /// functional sequences are looked up first, then current lang ones
+    fn parse_sequence(&self) -> InputEvent {
+        let config = &self.config;
+        let state = &self.state;
+
+        let Some(config_key) = config.lookup(state.lang.as_deref(), &state.sequence) else {
+            let is_lang_loaded = state
+                .lang
+                .as_ref()
+                .is_some_and(|lang| config.langs.contains_key(lang));
+            let reason = match is_lang_loaded {
+                true => SequenceRejectReason::InvalidSequence,
+                false => SequenceRejectReason::NoLangsLoaded,
+            };
+            return InputEvent::SeqRejected(state.sequence.clone(), reason);
+        };
+        InputEvent::SequenceParsed(
+            state.sequence.clone(),
+            InputEventKey {
+                key: config_key.clone(),
+                is_upper: state.is_upper_case,
+            },
+        )
+    }
+}
+
+/// Decodes timed inputs at once, text is written as by text sinks.
+///
+/// Ticks a minute after last input, so pending sequence and word complete.
+pub fn decode_inputs(config: Config, inputs: impl IntoIterator<Item = DecoderInput>) -> String {
+    let mut decoder = Decoder::new(config);
+    let mut output = TextBuffer::new();
+    let mut last_time = None;
+    for input in inputs {
+        last_time = Some(input.time());
+        for event in decoder.handle(input) {
+            output.write_event(&event, decoder.state());
+        }
+    }
+    if let Some(last_time) = last_time {
+        let tick = DecoderInput::Tick(last_time + Duration::from_secs(60));
+        for event in decoder.handle(tick) {
+            output.write_event(&event, decoder.state());
+        }
+    }
+    output.text.trim_end().to_string()
+}
+
+fn new_keyer(config: &Config) -> Option<Keyer> {
+    config.keyer.is_paddle().then(|| {
+        Keyer::new(config.keyer, config.timing().unit())
+            .with_memory(config.dit_memory, config.dah_memory)
+    })
+}
+
+/// returns hold duration if key was released, none otherwise
+fn update_key_state(state: &mut KeyState, event: &KeyEvent) -> Option<Duration> {
+    match (event.is_down, *state) {
+        (true, KeyState::NotPressed) => {
+            *state = KeyState::Down(event.time);
+            None
+        }
+        (false, KeyState::Down(time)) => {
+            *state = KeyState::NotPressed;
+            Some(event.time.saturating_duration_since(time))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_inputs, Decoder, DecoderInput};
+    use crate::morse::audio::keying;
+    use crate::morse::output::config_key_text;
+    use crate::morse::MorseKey::{self, Dash, Dot};
+    use crate::morse::{
+        Config, ConfigKey, ConfigLayoutKey, InputEvent, InputEventKey, InputWorkState, KeyCode,
+        KeyerMode, Timing,
+    };
+    use std::time::{Duration, Instant};
+
+    /// built-in config, its timing is set by durations only (100 ms and 750 ms)
+    fn durations_config() -> Config {
+        let mut config = Config::default_config();
+        config.lang = Some("en".into());
+        config
+    }
+
+    /// main key timeline of `text` sent by `timing`
+    fn straight_key_inputs(config: &Config, text: &str, timing: Timing) -> Vec<DecoderInput> {
+        let sequences = config.encode("en", text).unwrap();
+        let mut time = Instant::now();
+        let mut inputs = Vec::new();
+        for segment in keying(&sequences, &timing) {
+            inputs.push(match segment.is_down {
+                true => DecoderInput::KeyDown(config.main, time),
+                false => DecoderInput::KeyUp(config.main, time),
+            });
+            time += segment.duration;
+        }
+        inputs.push(DecoderInput::KeyUp(config.main, time));
+        inputs
+    }
+
+    #[test]
+    fn durations_do_not_stretch_gaps() {
+        let config = durations_config();
+        assert!(config.timing.is_none());
+        for wpm in [13.0, 15.0, 20.0, 25.0, 30.0] {
+            let inputs = straight_key_inputs(&config, "paris paris", Timing::new(wpm));
+            assert_eq!(
+                decode_inputs(config.clone(), inputs),
+                "paris paris",
+                "{} wpm",
+                wpm
+            );
+        }
+        // at 12 wpm dots are as long as threshold, first word only teaches it
+        let inputs = straight_key_inputs(&config, "paris paris paris", Timing::new(12.0));
+        let text = decode_inputs(config, inputs);
+        assert!(text.ends_with(" paris paris"), "{:?}", text);
+    }
+
+    /// decoder driven by times in ms since start
+    struct Timeline {
+        decoder: Decoder,
+        start: Instant,
+    }
+
+    impl Timeline {
+        /// built-in config with fixed 100 ms threshold:
+        /// character gap is 100 ms and word gap is 250 ms
+        fn new() -> Self {
+            let mut config = Config::default_config();
+            config.lang = Some("en".into());
+            config.lock_long_press = true;
+            Timeline {
+                decoder: Decoder::new(config),
+                start: Instant::now(),
+            }
+        }
+
+        fn at(&self, ms: u64) -> Instant {
+            self.start + Duration::from_millis(ms)
+        }
+
+        fn down(&mut self, key: KeyCode, ms: u64) -> Vec<InputEvent> {
+            self.decoder.handle(DecoderInput::KeyDown(key, self.at(ms)))
+        }
+
+        fn up(&mut self, key: KeyCode, ms: u64) -> Vec<InputEvent> {
+            self.decoder.handle(DecoderInput::KeyUp(key, self.at(ms)))
+        }
+
+        fn tick(&mut self, ms: u64) -> Vec<InputEvent> {
+            self.decoder.handle(DecoderInput::Tick(self.at(ms)))
+        }
+
+        /// events of key press from `ms` to `ms + hold`
+        fn press(&mut self, key: KeyCode, ms: u64, hold: u64) -> Vec<InputEvent> {
+            let mut events = self.down(key, ms);
+            events.extend(self.up(key, ms + hold));
+            events
+        }
+
+        fn main(&self) -> KeyCode {
+            self.decoder.config().main
+        }
+    }
+
+    fn morse_keys(events: &[InputEvent]) -> Vec<MorseKey> {
+        events
+            .iter()
+            .filter_map(|event| match event {
+                InputEvent::MorseKey(key) => Some(*key),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// text and case of parsed sequences
+    fn parsed(events: &[InputEvent]) -> Vec<(String, bool)> {
+        events
+            .iter()
+            .filter_map(|event| match event {
+                InputEvent::SequenceParsed(_, InputEventKey { key, is_upper }) => {
+                    Some((config_key_text(key), *is_upper))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn presses_shorter_than_threshold_are_dots() {
+        let mut timeline = Timeline::new();
+        let main = timeline.main();
+        assert_eq!(morse_keys(&timeline.press(main, 0, 99)), [Dot]);
+        assert_eq!(morse_keys(&timeline.press(main, 150, 100)), [Dash]);
+        assert_eq!(morse_keys(&timeline.press(main, 300, 40)), [Dot]);
+    }
+
+    #[test]
+    fn sequence_is_accepted_after_character_gap() {
+        let mut timeline = Timeline::new();
+        let main = timeline.main();
+        timeline.press(main, 0, 40);
+        timeline.press(main, 90, 150);
+        // released at 240 ms
+        assert!(timeline.tick(330).is_empty());
+        assert_eq!(
+            timeline.tick(350),
+            [InputEvent::SequenceParsed(
+                vec![Dot, Dash],
+                InputEventKey {
+                    key: ConfigKey::Layout(ConfigLayoutKey {
+                        lower: KeyCode::Layout('a'),
+                    }),
+                    is_upper: false,
+                },
+            )]
+        );
+        assert!(timeline.decoder.state().sequence.is_empty());
+    }
+
+    #[test]
+    fn next_press_accepts_sequence_without_ticks() {
+        let mut timeline = Timeline::new();
+        let main = timeline.main();
+        timeline.press(main, 0, 40);
+        let events = timeline.press(main, 200, 40);
+        assert_eq!(parsed(&events), [("e".to_string(), false)]);
+        assert_eq!(morse_keys(&events), [Dot]);
+    }
+
+    #[test]
+    fn word_separator_follows_word_gap() {
+        let mut timeline = Timeline::new();
+        let main = timeline.main();
+        timeline.press(main, 0, 40);
+        assert_eq!(parsed(&timeline.tick(200)), [("e".to_string(), false)]);
+        assert!(timeline.tick(280).is_empty());
+        let events = timeline.tick(300);
+        assert!(matches!(
+            events.as_slice(),
+            [InputEvent::WordSeparator(InputEventKey { key, is_upper: false })]
+                if config_key_text(key) == " "
+        ));
+        // only once
+        assert!(timeline.tick(1000).is_empty());
+    }
+
+    #[test]
+    fn unknown_sequence_is_rejected() {
+        let mut timeline = Timeline::new();
+        let main = timeline.main();
+        for ms in [0, 100, 200, 300, 400, 500, 600, 700] {
+            timeline.press(main, ms, 40);
+        }
+        let events = timeline.tick(2000);
+        assert!(matches!(
+            events.as_slice(),
+            [InputEvent::SeqRejected(sequence, _)] if sequence.len() == 8
+        ));
+    }
+
+    #[test]
+    fn change_lang_key_cycles_langs() {
+        let mut timeline = Timeline::new();
+        let change_lang = timeline.decoder.config().change_lang;
+        assert!(timeline.down(change_lang, 0).is_empty());
+        assert_eq!(
+            timeline.up(change_lang, 50),
+            [InputEvent::LangChange("ru".into())]
+        );
+        let main = timeline.main();
+        timeline.press(main, 100, 40);
+        assert_eq!(parsed(&timeline.tick(1000)), [("е".to_string(), false)]);
+        assert_eq!(
+            timeline.press(change_lang, 2000, 50),
+            [InputEvent::LangChange("en".into())]
+        );
+    }
+
+    #[test]
+    fn change_case_key_toggles_upper_case() {
+        let mut timeline = Timeline::new();
+        let change_case = timeline.decoder.config().change_case;
+        assert_eq!(
+            timeline.press(change_case, 0, 50),
+            [InputEvent::CaseChange(true)]
+        );
+        let main = timeline.main();
+        timeline.press(main, 100, 40);
+        assert_eq!(parsed(&timeline.tick(1000)), [("e".to_string(), true)]);
+        assert_eq!(
+            timeline.press(change_case, 2000, 50),
+            [InputEvent::CaseChange(false)]
+        );
+    }
+
+    #[test]
+    fn pause_key_stops_decoding_until_pressed_again() {
+        let mut timeline = Timeline::new();
+        let pause = timeline.decoder.config().pause;
+        let main = timeline.main();
+        assert_eq!(
+            timeline.press(pause, 0, 50),
+            [InputEvent::PauseToggle(true)]
+        );
+        assert_eq!(timeline.decoder.state().work_state, InputWorkState::Pause);
+        let events = timeline.press(main, 100, 40);
+        assert!(timeline.tick(1000).is_empty());
+        assert!(morse_keys(&events).is_empty());
+
+        assert_eq!(
+            timeline.press(pause, 2000, 50),
+            [InputEvent::PauseToggle(false)]
+        );
+        assert_eq!(morse_keys(&timeline.press(main, 2100, 40)), [Dot]);
+    }
+
+    #[test]
+    fn exit_key_stops_decoder() {
+        let mut timeline = Timeline::new();
+        let exit = timeline.decoder.config().exit;
+        let main = timeline.main();
+        timeline.press(main, 0, 40);
+        let events = timeline.press(exit, 1000, 50);
+        // pending sequence is accepted before exit
+        assert_eq!(parsed(&events), [("e".to_string(), false)]);
+        assert_eq!(events.last(), Some(&InputEvent::Exit));
+        assert_eq!(timeline.decoder.state().work_state, InputWorkState::Exit);
+        assert!(timeline.press(main, 2000, 40).is_empty());
+    }
+
+    #[test]
+    fn langs_start_and_cycle_in_sorted_order() {
+        let mut config = Config::default_config();
+        config.lang = None;
+        let first = config.langs["en"].clone();
+        config.langs.insert("de".into(), first.clone());
+        config.langs.insert("fr".into(), first);
+        assert_eq!(config.start_lang().map(String::as_str), Some("de"));
+
+        let change_lang = config.change_lang;
+        let mut timeline = Timeline::new();
+        timeline.decoder = Decoder::new(config);
+        let mut langs = Vec::new();
+        for ms in [0, 100, 200, 300] {
+            for event in timeline.press(change_lang, ms, 50) {
+                if let InputEvent::LangChange(lang) = event {
+                    langs.push(lang);
+                }
+            }
+        }
+        assert_eq!(langs, ["en", "fr", "ru", "de"]);
+    }
+
+    #[test]
+    fn replaced_keyer_releases_keying_and_keeps_held_paddle() {
+        let mut config = durations_config();
+        config.keyer = KeyerMode::IambicA;
+        config.dit = Some(KeyCode::Layout('z'));
+        config.dah = Some(KeyCode::Layout('x'));
+        config.timing = Some(Timing::new(20.0));
+        let mut timeline = Timeline::new();
+        timeline.decoder = Decoder::new(config.clone());
+        let dit = KeyCode::Layout('z');
+
+        // dot of 60 ms unit is in progress at 30 ms
+        let mut events = timeline.down(dit, 0);
+        events.extend(timeline.tick(30));
+        assert!(events.contains(&InputEvent::KeyingChange(true, timeline.at(0))));
+
+        // 12 wpm has 100 ms unit
+        config.timing = Some(Timing::new(12.0));
+        let events = timeline.decoder.replace_config(config, timeline.at(30));
+        assert_eq!(events, [InputEvent::KeyingChange(false, timeline.at(30))]);
+        assert!(!timeline.decoder.state().is_keyed);
+
+        let mut events = timeline.tick(40);
+        assert_eq!(events, [InputEvent::KeyingChange(true, timeline.at(30))]);
+        events.extend(timeline.up(dit, 150));
+        events.extend(timeline.tick(1000));
+        assert!(events.contains(&InputEvent::KeyingChange(false, timeline.at(130))));
+        assert_eq!(morse_keys(&events), [Dot]);
+        assert!(!timeline.decoder.state().is_keyed);
+    }
+}
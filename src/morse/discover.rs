@@ -0,0 +1,80 @@
+use super::{Config, ConfigError};
+use std::env;
+use std::fmt;
+use std::path::PathBuf;
+
+/// `en` and `ru` langs with functional keys available on every platform
+pub const DEFAULT_CONFIG: &str = include_str!("../../other/default_config.toml");
+
+const CONFIG_DIR: &str = "morse";
+const CONFIG_FILE: &str = "config.toml";
+
+/// Where config comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigSource {
+    File(PathBuf),
+    /// no config file found, `DEFAULT_CONFIG` is used
+    Default,
+}
+
+impl fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigSource::File(path) => write!(f, "{}", path.display()),
+            ConfigSource::Default => write!(f, "built-in config"),
+        }
+    }
+}
+
+/// Config locations in search order:
+/// `$XDG_CONFIG_HOME/morse/config.toml` (`$HOME/.config` if not set),
+/// `%APPDATA%\morse\config.toml`, `config.toml` of current directory.
+pub fn config_paths() -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+
+    let xdg_config_home = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| {
+            env::var_os("HOME")
+                .filter(|dir| !dir.is_empty())
+                .map(|home| PathBuf::from(home).join(".config"))
+        });
+    if let Some(dir) = xdg_config_home {
+        paths.push(dir.join(CONFIG_DIR).join(CONFIG_FILE));
+    }
+    if let Some(dir) = env::var_os("APPDATA").filter(|dir| !dir.is_empty()) {
+        paths.push(PathBuf::from(dir).join(CONFIG_DIR).join(CONFIG_FILE));
+    }
+    paths.push(PathBuf::from(CONFIG_FILE));
+
+    paths
+}
+
+/// first existing file of `config_paths`
+pub fn find_config() -> ConfigSource {
+    config_paths()
+        .into_iter()
+        .find(|path| path.is_file())
+        .map_or(ConfigSource::Default, ConfigSource::File)
+}
+
+impl Config {
+    /// Built-in config, see `DEFAULT_CONFIG`.
+    pub fn default_config() -> Config {
+        Config::from_toml(DEFAULT_CONFIG).expect("default config is valid")
+    }
+
+    pub fn load_from(source: &ConfigSource) -> Result<Config, ConfigError> {
+        match source {
+            ConfigSource::File(path) => Config::load(path),
+            ConfigSource::Default => Ok(Config::default_config()),
+        }
+    }
+
+    /// Loads first found config file, built-in config if there are none.
+    pub fn discover() -> Result<(Config, ConfigSource), ConfigError> {
+        let source = find_config();
+        Config::load_from(&source).map(|config| (config, source))
+    }
+}
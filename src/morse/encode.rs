@@ -0,0 +1,194 @@
+use super::output::config_key_text;
+use super::{morse_seq_to_string, Config, MorseSequence};
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EncodeError {
+    UnknownLang(String),
+    /// characters without sequence with their positions (in characters) in text
+    UnmappedCharacters(Vec<(usize, char)>),
+}
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncodeError::UnknownLang(lang) => write!(f, "lang {:?} is not defined", lang),
+            EncodeError::UnmappedCharacters(characters) => {
+                let characters = characters
+                    .iter()
+                    .map(|(position, character)| format!("{:?} at {}", character, position))
+                    .collect::<Vec<_>>();
+                write!(f, "no sequence for {}", characters.join(", "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for EncodeError {}
+
+/// Dots and dashes separated by spaces, word gaps (empty sequences) are ` / `.
+pub fn format_sequences(sequences: &[MorseSequence]) -> String {
+    sequences
+        .iter()
+        .map(|seq| match seq.is_empty() {
+            true => "/".to_string(),
+            false => morse_seq_to_string(seq),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+impl Config {
+    /// Text typed by sequences of `lang` and `functional`, mapped to shortest sequence.
+    ///
+    /// Lang sequences shadowed by functional ones are skipped, as they are never decoded.
+    pub fn encoding_table(
+        &self,
+        lang: &str,
+    ) -> Result<HashMap<String, MorseSequence>, EncodeError> {
+        let keys = self
+            .langs
+            .get(lang)
+            .ok_or_else(|| EncodeError::UnknownLang(lang.to_string()))?;
+
+        let mut table: HashMap<String, MorseSequence> = HashMap::new();
+        let entries = self.functional.iter().chain(
+            keys.iter()
+                .filter(|(seq, _)| !self.functional.contains_key(*seq)),
+        );
+        for (seq, key) in entries {
+            let text = config_key_text(key);
+            if text.is_empty() {
+                continue;
+            }
+            let is_better = table.get(&text).is_none_or(|other| {
+                (seq.len(), morse_seq_to_string(seq)) < (other.len(), morse_seq_to_string(other))
+            });
+            if is_better {
+                table.insert(text, seq.clone());
+            }
+        }
+        Ok(table)
+    }
+
+    /// Encodes text with sequences of `lang` and `functional`, longest matching text first.
+    ///
+    /// Case is folded like layout keys of config are. Every run of whitespace
+    /// is a word gap, encoded as empty sequence.
+    pub fn encode(&self, lang: &str, text: &str) -> Result<Vec<MorseSequence>, EncodeError> {
+        let table = self.encoding_table(lang)?;
+        let max_len = table
+            .keys()
+            .map(|text| text.chars().count())
+            .max()
+            .unwrap_or(0);
+
+        let original = text.chars().collect::<Vec<_>>();
+        let chars = original
+            .iter()
+            .map(|&character| character.to_lowercase().next().unwrap_or(character))
+            .collect::<Vec<_>>();
+        let mut sequences = Vec::new();
+        let mut unmapped = Vec::new();
+        let mut position = 0;
+        while position < chars.len() {
+            if chars[position].is_whitespace() {
+                if sequences
+                    .last()
+                    .is_some_and(|seq: &MorseSequence| !seq.is_empty())
+                {
+                    sequences.push(Vec::new());
+                }
+                position += 1;
+                continue;
+            }
+            let longest = (1..=max_len.min(chars.len() - position))
+                .rev()
+                .find_map(|len| {
+                    let text = chars[position..position + len].iter().collect::<String>();
+                    table.get(&text).map(|seq| (len, seq))
+                });
+            match longest {
+                Some((len, seq)) => {
+                    sequences.push(seq.clone());
+                    position += len;
+                }
+                None => {
+                    unmapped.push((position, original[position]));
+                    position += 1;
+                }
+            }
+        }
+        if !unmapped.is_empty() {
+            return Err(EncodeError::UnmappedCharacters(unmapped));
+        }
+        if sequences.last().is_some_and(|seq| seq.is_empty()) {
+            sequences.pop();
+        }
+        Ok(sequences)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_sequences, EncodeError};
+    use crate::morse::{morse_seq_from_string, Config, ConfigKey, ConfigLayoutKey, KeyCode};
+
+    fn encode(config: &Config, lang: &str, text: &str) -> Result<String, EncodeError> {
+        config
+            .encode(lang, text)
+            .map(|sequences| format_sequences(&sequences))
+    }
+
+    #[test]
+    fn case_is_folded() {
+        let config = Config::default_config();
+        assert_eq!(encode(&config, "en", "SoS").unwrap(), "... --- ...");
+        assert_eq!(
+            encode(&config, "ru", "ПрИвЕт"),
+            encode(&config, "ru", "привет")
+        );
+    }
+
+    #[test]
+    fn unmapped_characters_are_reported_at_char_positions() {
+        let config = Config::default_config();
+        let err = config.encode("en", "Й a#b").unwrap_err();
+        assert_eq!(
+            err,
+            EncodeError::UnmappedCharacters(vec![(0, 'Й'), (3, '#')])
+        );
+        assert_eq!(err.to_string(), "no sequence for 'Й' at 0, '#' at 3");
+        assert_eq!(
+            config.encode("de", "a"),
+            Err(EncodeError::UnknownLang("de".into()))
+        );
+    }
+
+    #[test]
+    fn whitespace_runs_are_word_gaps() {
+        let config = Config::default_config();
+        assert_eq!(encode(&config, "en", "  e \t\n t  ").unwrap(), ". / -");
+        assert_eq!(encode(&config, "en", " \n ").unwrap(), "");
+    }
+
+    #[test]
+    fn lang_sequences_shadowed_by_functional_are_skipped() {
+        let mut config = Config::default_config();
+        // ".-.--" is functional Backspace
+        let shadowed = morse_seq_from_string(".-.--").unwrap();
+        assert!(config.functional.contains_key(&shadowed));
+        let key = ConfigKey::Layout(ConfigLayoutKey {
+            lower: KeyCode::Layout('~'),
+        });
+        config.langs.get_mut("en").unwrap().insert(shadowed, key);
+
+        let table = config.encoding_table("en").unwrap();
+        assert!(!table.contains_key("~"));
+        assert_eq!(
+            config.encode("en", "~"),
+            Err(EncodeError::UnmappedCharacters(vec![(0, '~')]))
+        );
+    }
+}
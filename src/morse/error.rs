@@ -0,0 +1,278 @@
+use super::{KeyerMode, Timing, Tone};
+use std::fmt;
+
+/// 1-based position in config source
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConfigLocation {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for ConfigLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    /// config file can not be read
+    Io(std::io::Error),
+    /// not a toml or does not match `ConfigSerde`, error contains its location
+    Parse(toml::de::Error),
+    /// sequence contains something other than `.` and `-`
+    InvalidSequence {
+        /// none for `functional` sequences
+        lang: Option<String>,
+        sequence: String,
+        character: char,
+        /// known only if config is loaded from source text
+        location: Option<ConfigLocation>,
+    },
+    /// neither field nor `timing` is set
+    MissingField(&'static str),
+    /// duration field is zero, e.g. `time_to_long_press`
+    ZeroDuration(&'static str),
+    /// speed is not positive or effective speed is above character one
+    InvalidTiming(Timing),
+    /// paddle keyer mode without `dit` or `dah` key
+    MissingPaddles(KeyerMode),
+    /// frequency is not positive or volume is out of 0 to 1
+    InvalidTone(Tone),
+}
+
+impl ConfigError {
+    /// Fills location of invalid sequence by searching its key in toml `source`.
+    pub(super) fn locate(mut self, source: &str) -> Self {
+        if let ConfigError::InvalidSequence {
+            lang,
+            sequence,
+            location,
+            ..
+        } = &mut self
+        {
+            let table = match lang {
+                Some(lang) => format!("langs.{}", lang),
+                None => "functional".to_string(),
+            };
+            let quoted = format!("\"{}\"", sequence);
+            // dotted key (`[langs.ru.".-x".Layout]`) first, any quoted occurrence otherwise
+            let offset = source
+                .find(&format!("{}.{}", table, quoted))
+                .map(|offset| offset + table.len() + 1)
+                .or_else(|| source.find(&quoted));
+            *location = offset.map(|offset| {
+                let before = &source[..offset];
+                let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+                ConfigLocation {
+                    line: before.matches('\n').count() + 1,
+                    column: before[line_start..].chars().count() + 1,
+                }
+            });
+        }
+        self
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(err) => write!(f, "failed to read config: {}", err),
+            ConfigError::Parse(err) => write!(f, "failed to parse config: {}", err),
+            ConfigError::InvalidSequence {
+                lang,
+                sequence,
+                character,
+                location,
+            } => {
+                write!(
+                    f,
+                    "invalid character {:?} in sequence {:?} (only '.' and '-' are allowed)",
+                    character, sequence
+                )?;
+                match lang {
+                    Some(lang) => write!(f, " of lang {:?}", lang)?,
+                    None => write!(f, " of functional keys")?,
+                }
+                if let Some(location) = location {
+                    write!(f, " at {}", location)?;
+                }
+                Ok(())
+            }
+            ConfigError::MissingField(field) => {
+                write!(f, "neither `{}` nor `timing` is set", field)
+            }
+            ConfigError::ZeroDuration(field) => write!(f, "`{}` must be above zero", field),
+            ConfigError::InvalidTiming(timing) => match timing.farnsworth_wpm {
+                Some(farnsworth_wpm) => write!(
+                    f,
+                    "invalid timing: wpm {} and farnsworth_wpm {} must be positive, \
+                     farnsworth_wpm must not exceed wpm",
+                    timing.wpm, farnsworth_wpm
+                ),
+                None => write!(f, "invalid timing: wpm {} must be positive", timing.wpm),
+            },
+            ConfigError::MissingPaddles(keyer) => {
+                write!(f, "keyer {:?} requires `dit` and `dah` keys", keyer)
+            }
+            ConfigError::InvalidTone(tone) => write!(
+                f,
+                "invalid tone: frequency {} must be positive, volume {} must be within 0 and 1",
+                tone.frequency, tone.volume
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConfigError::Io(err) => Some(err),
+            ConfigError::Parse(err) => Some(err),
+            ConfigError::InvalidSequence { .. }
+            | ConfigError::MissingField(_)
+            | ConfigError::ZeroDuration(_)
+            | ConfigError::InvalidTiming(_)
+            | ConfigError::MissingPaddles(_)
+            | ConfigError::InvalidTone(_) => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for ConfigError {
+    fn from(err: std::io::Error) -> Self {
+        ConfigError::Io(err)
+    }
+}
+
+impl From<toml::de::Error> for ConfigError {
+    fn from(err: toml::de::Error) -> Self {
+        ConfigError::Parse(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ConfigError, ConfigLocation};
+    use crate::morse::discover::DEFAULT_CONFIG;
+    use crate::morse::{Config, KeyerMode, Timing, Tone};
+    use std::time::Duration;
+
+    #[test]
+    fn invalid_lang_sequence_is_located_by_dotted_key() {
+        let source = format!(
+            "{}\n[langs.ru.\".-x\".Layout]\nlower.Layout = \"ж\"\n",
+            DEFAULT_CONFIG
+        );
+        let line = DEFAULT_CONFIG.lines().count() + 2;
+        let err = Config::from_toml(&source).unwrap_err();
+        match &err {
+            ConfigError::InvalidSequence {
+                lang,
+                sequence,
+                character,
+                location,
+            } => {
+                assert_eq!(lang.as_deref(), Some("ru"));
+                assert_eq!(sequence, ".-x");
+                assert_eq!(*character, 'x');
+                assert_eq!(*location, Some(ConfigLocation { line, column: 11 }));
+            }
+            other => panic!("unexpected error: {}", other),
+        }
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "invalid character 'x' in sequence \".-x\" (only '.' and '-' are allowed) \
+                 of lang \"ru\" at line {}, column 11",
+                line
+            )
+        );
+    }
+
+    #[test]
+    fn io_error_is_displayed() {
+        let err = ConfigError::from(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "no config",
+        ));
+        assert_eq!(err.to_string(), "failed to read config: no config");
+    }
+
+    #[test]
+    fn parse_error_is_displayed() {
+        let err = Config::from_toml("main = ").unwrap_err();
+        assert!(matches!(err, ConfigError::Parse(_)));
+        assert!(err.to_string().starts_with("failed to parse config: "));
+    }
+
+    #[test]
+    fn functional_sequence_error_is_displayed() {
+        let err = ConfigError::InvalidSequence {
+            lang: None,
+            sequence: "..,".into(),
+            character: ',',
+            location: None,
+        };
+        assert_eq!(
+            err.to_string(),
+            "invalid character ',' in sequence \"..,\" (only '.' and '-' are allowed) \
+             of functional keys"
+        );
+    }
+
+    #[test]
+    fn missing_field_is_displayed() {
+        let err = ConfigError::MissingField("time_to_long_press");
+        assert_eq!(
+            err.to_string(),
+            "neither `time_to_long_press` nor `timing` is set"
+        );
+    }
+
+    #[test]
+    fn zero_duration_is_displayed() {
+        let err = ConfigError::ZeroDuration("accept_sequence_delay");
+        assert_eq!(
+            err.to_string(),
+            "`accept_sequence_delay` must be above zero"
+        );
+    }
+
+    #[test]
+    fn invalid_timing_is_displayed() {
+        let err = ConfigError::InvalidTiming(Timing::new(0.0));
+        assert_eq!(err.to_string(), "invalid timing: wpm 0 must be positive");
+        let err = ConfigError::InvalidTiming(Timing {
+            wpm: 10.0,
+            farnsworth_wpm: Some(20.0),
+        });
+        assert_eq!(
+            err.to_string(),
+            "invalid timing: wpm 10 and farnsworth_wpm 20 must be positive, \
+             farnsworth_wpm must not exceed wpm"
+        );
+    }
+
+    #[test]
+    fn missing_paddles_are_displayed() {
+        let err = ConfigError::MissingPaddles(KeyerMode::IambicB);
+        assert_eq!(
+            err.to_string(),
+            "keyer IambicB requires `dit` and `dah` keys"
+        );
+    }
+
+    #[test]
+    fn invalid_tone_is_displayed() {
+        let err = ConfigError::InvalidTone(Tone {
+            frequency: 600.0,
+            volume: 2.0,
+            ramp: Duration::from_millis(5),
+        });
+        assert_eq!(
+            err.to_string(),
+            "invalid tone: frequency 600 must be positive, volume 2 must be within 0 and 1"
+        );
+    }
+}
@@ -0,0 +1,380 @@
+use super::MorseKey;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+/// How `main` or `dit` and `dah` keys turn into dots and dashes.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum KeyerMode {
+    /// `main` key, hold duration decides dot or dash
+    #[default]
+    Straight,
+    /// `dit` and `dah` paddles repeat their elements while held and alternate
+    /// them while squeezed, sending stops with element in progress once paddles are released
+    IambicA,
+    /// same as `IambicA`, but if paddles were squeezed during element,
+    /// one more alternate element is sent after they are released
+    IambicB,
+    /// like iambic, but while squeezed last pressed paddle repeats its element
+    /// instead of alternating, e.g. holding `dah` and then squeezing `dit` sends `-...`
+    Ultimatic,
+    /// single lever can not be squeezed: if both keys are down, first pressed one wins,
+    /// e.g. holding `dah` and then pressing `dit` sends `---` until `dah` is released
+    SingleLever,
+    /// semi-automatic (Vibroplex-style) key: `dit` repeats dots while held,
+    /// `dah` is a manual contact whose hold duration decides dot or dash like `main` one
+    Bug,
+}
+
+impl KeyerMode {
+    /// true if `dit` and `dah` keys are used instead of `main`
+    pub fn is_paddle(&self) -> bool {
+        *self != KeyerMode::Straight
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Paddle {
+    Dit,
+    Dah,
+}
+
+/// Element keyed by keyer, key is down from `start` to `end`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyerElement {
+    pub key: MorseKey,
+    pub start: Instant,
+    pub end: Instant,
+}
+
+/// Automatic paddle keyer, only `dit` is automatic in `Bug` mode.
+///
+/// Dot is 1 unit, dash is 3 units, every element is followed by 1 unit gap.
+/// Next element is chosen by paddles at the end of that gap, so element in progress
+/// is always completed. Like `Decoder`, it takes all time from inputs:
+/// paddle changes and ticks must come in time order.
+///
+/// E.g. with unit of 60 ms in `IambicA` mode, squeezing `dah` at 0 ms and `dit` at 100 ms
+/// and releasing both at 250 ms sends dash 0-180 ms and dot 240-300 ms.
+/// In `IambicB` mode dash 360-540 ms follows them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keyer {
+    mode: KeyerMode,
+    unit: Duration,
+    is_dit_down: bool,
+    is_dah_down: bool,
+    /// element in progress, until end of its gap
+    element: Option<KeyerElement>,
+    /// element in progress is returned once its key is up
+    is_element_sent: bool,
+    /// alternated while squeezed
+    last_key: Option<MorseKey>,
+    /// last paddle that went down
+    last_pressed: Option<Paddle>,
+    /// both paddles were down during element in progress
+    is_squeezed: bool,
+    /// remember paddle presses during element in progress
+    is_dit_memory: bool,
+    is_dah_memory: bool,
+    /// paddle was pressed during element in progress, it is sent next even if released
+    dit_memory: bool,
+    dah_memory: bool,
+}
+
+impl Keyer {
+    /// panics if `unit` is zero, elements would never end
+    pub fn new(mode: KeyerMode, unit: Duration) -> Self {
+        assert!(!unit.is_zero(), "keyer unit must not be zero");
+        Keyer {
+            mode,
+            unit,
+            is_dit_down: false,
+            is_dah_down: false,
+            element: None,
+            is_element_sent: false,
+            last_key: None,
+            last_pressed: None,
+            is_squeezed: false,
+            is_dit_memory: false,
+            is_dah_memory: false,
+            dit_memory: false,
+            dah_memory: false,
+        }
+    }
+
+    /// Enables dit and dah memory: paddle tapped during element (or gap after it)
+    /// is sent after it, e.g. with dit memory tapping `dit` during dash sends `-.`.
+    pub fn with_memory(mut self, is_dit_memory: bool, is_dah_memory: bool) -> Self {
+        self.is_dit_memory = is_dit_memory;
+        self.is_dah_memory = is_dah_memory;
+        self
+    }
+
+    pub fn mode(&self) -> KeyerMode {
+        self.mode
+    }
+
+    pub fn unit(&self) -> Duration {
+        self.unit
+    }
+
+    /// false while element or gap after it is in progress
+    pub fn is_idle(&self) -> bool {
+        self.element.is_none()
+    }
+
+    /// start of element whose key is still down at last input time
+    pub fn pending_element_start(&self) -> Option<Instant> {
+        self.element
+            .filter(|_| !self.is_element_sent)
+            .map(|element| element.start)
+    }
+
+    /// true if paddle is held, `dah` is never held in `Bug` mode
+    pub fn is_pressed(&self, paddle: Paddle) -> bool {
+        match paddle {
+            Paddle::Dit => self.is_dit_down,
+            Paddle::Dah => self.is_dah_down,
+        }
+    }
+
+    /// Changes paddle state, returns elements completed until `time`.
+    /// `dah` is ignored in `Bug` mode.
+    pub fn press(&mut self, paddle: Paddle, is_down: bool, time: Instant) -> Vec<KeyerElement> {
+        let elements = self.tick(time);
+        if self.mode == KeyerMode::Bug && paddle == Paddle::Dah {
+            return elements;
+        }
+        match paddle {
+            Paddle::Dit => self.is_dit_down = is_down,
+            Paddle::Dah => self.is_dah_down = is_down,
+        }
+        if is_down {
+            self.last_pressed = Some(paddle);
+            if self.element.is_some() {
+                match paddle {
+                    Paddle::Dit => self.dit_memory |= self.is_dit_memory,
+                    Paddle::Dah => self.dah_memory |= self.is_dah_memory,
+                }
+            }
+        }
+        if self.is_dit_down && self.is_dah_down && self.element.is_some() {
+            self.is_squeezed = true;
+        }
+        if self.element.is_none() && is_down {
+            self.start_next(time, false);
+        }
+        elements
+    }
+
+    /// Returns elements completed until `time`, starting new ones while paddles are held.
+    pub fn tick(&mut self, time: Instant) -> Vec<KeyerElement> {
+        let mut elements = Vec::new();
+        while let Some(element) = self.element {
+            if !self.is_element_sent && element.end <= time {
+                elements.push(element);
+                self.is_element_sent = true;
+            }
+            let gap_end = element.end + self.unit;
+            if gap_end > time {
+                break;
+            }
+            self.element = None;
+            self.start_next(gap_end, true);
+        }
+        elements
+    }
+
+    /// `is_after_element` - previous element has just ended, not idle
+    fn start_next(&mut self, time: Instant, is_after_element: bool) {
+        let Some(key) = self.next_key(is_after_element) else {
+            return;
+        };
+        let length = match key {
+            MorseKey::Dot => self.unit,
+            MorseKey::Dash => self.unit * 3,
+        };
+        self.element = Some(KeyerElement {
+            key,
+            start: time,
+            end: time + length,
+        });
+        self.is_element_sent = false;
+        self.last_key = Some(key);
+        self.is_squeezed = self.is_dit_down && self.is_dah_down;
+        self.dit_memory = false;
+        self.dah_memory = false;
+    }
+
+    fn next_key(&self, is_after_element: bool) -> Option<MorseKey> {
+        let alternate = match self.last_key {
+            Some(MorseKey::Dot) => MorseKey::Dash,
+            Some(MorseKey::Dash) | None => MorseKey::Dot,
+        };
+        let last_pressed = match self.last_pressed {
+            Some(Paddle::Dit) | None => MorseKey::Dot,
+            Some(Paddle::Dah) => MorseKey::Dash,
+        };
+        let first_pressed = match last_pressed {
+            MorseKey::Dot => MorseKey::Dash,
+            MorseKey::Dash => MorseKey::Dot,
+        };
+        // remembered paddles count as held
+        let is_dit_down = self.is_dit_down || (self.dit_memory && is_after_element);
+        let is_dah_down = self.is_dah_down || (self.dah_memory && is_after_element);
+        match (is_dit_down, is_dah_down) {
+            (true, true) => match self.mode {
+                KeyerMode::Ultimatic => Some(last_pressed),
+                KeyerMode::SingleLever => Some(first_pressed),
+                _ => Some(alternate),
+            },
+            (true, false) => Some(MorseKey::Dot),
+            (false, true) => Some(MorseKey::Dash),
+            (false, false) => match self.mode {
+                // squeeze released during element
+                KeyerMode::IambicB if self.is_squeezed && is_after_element => Some(alternate),
+                _ => None,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Keyer, KeyerMode, MorseKey, Paddle};
+    use std::time::{Duration, Instant};
+    use MorseKey::{Dash, Dot};
+
+    /// elements sent by keyer with 60 ms unit for `(ms, paddle, is_down)` changes,
+    /// as `(key, start ms, end ms)`
+    fn send(mut keyer: Keyer, presses: &[(u64, Paddle, bool)]) -> Vec<(MorseKey, u64, u64)> {
+        let start = Instant::now();
+        let ms = |time: Instant| time.duration_since(start).as_millis() as u64;
+        let mut elements = Vec::new();
+        for &(time, paddle, is_down) in presses {
+            elements.extend(keyer.press(paddle, is_down, start + Duration::from_millis(time)));
+        }
+        elements.extend(keyer.tick(start + Duration::from_secs(10)));
+        elements
+            .into_iter()
+            .map(|element| (element.key, ms(element.start), ms(element.end)))
+            .collect()
+    }
+
+    fn keyer(mode: KeyerMode) -> Keyer {
+        Keyer::new(mode, Duration::from_millis(60))
+    }
+
+    /// `dah` at 0 ms, `dit` at 100 ms, both released at 250 ms
+    const SQUEEZE: [(u64, Paddle, bool); 4] = [
+        (0, Paddle::Dah, true),
+        (100, Paddle::Dit, true),
+        (250, Paddle::Dit, false),
+        (250, Paddle::Dah, false),
+    ];
+
+    #[test]
+    #[should_panic(expected = "keyer unit must not be zero")]
+    fn zero_unit_is_rejected() {
+        Keyer::new(KeyerMode::IambicA, Duration::ZERO);
+    }
+
+    #[test]
+    fn iambic_a_stops_with_element_in_progress() {
+        let elements = send(keyer(KeyerMode::IambicA), &SQUEEZE);
+        assert_eq!(elements, [(Dash, 0, 180), (Dot, 240, 300)]);
+    }
+
+    #[test]
+    fn iambic_b_sends_alternate_element_after_squeeze() {
+        let elements = send(keyer(KeyerMode::IambicB), &SQUEEZE);
+        assert_eq!(
+            elements,
+            [(Dash, 0, 180), (Dot, 240, 300), (Dash, 360, 540)]
+        );
+    }
+
+    #[test]
+    fn held_paddle_repeats_its_element() {
+        let presses = [(0, Paddle::Dit, true), (250, Paddle::Dit, false)];
+        let elements = send(keyer(KeyerMode::IambicA), &presses);
+        assert_eq!(elements, [(Dot, 0, 60), (Dot, 120, 180), (Dot, 240, 300)]);
+    }
+
+    #[test]
+    fn ultimatic_repeats_last_pressed_paddle() {
+        let presses = [
+            (0, Paddle::Dah, true),
+            (100, Paddle::Dit, true),
+            (590, Paddle::Dit, false),
+            (590, Paddle::Dah, false),
+        ];
+        let elements = send(keyer(KeyerMode::Ultimatic), &presses);
+        assert_eq!(
+            elements,
+            [
+                (Dash, 0, 180),
+                (Dot, 240, 300),
+                (Dot, 360, 420),
+                (Dot, 480, 540)
+            ]
+        );
+    }
+
+    #[test]
+    fn single_lever_keeps_first_pressed_paddle() {
+        let presses = [
+            (0, Paddle::Dah, true),
+            (100, Paddle::Dit, true),
+            (600, Paddle::Dah, false),
+            (800, Paddle::Dit, false),
+        ];
+        let elements = send(keyer(KeyerMode::SingleLever), &presses);
+        assert_eq!(
+            elements,
+            [
+                (Dash, 0, 180),
+                (Dash, 240, 420),
+                (Dash, 480, 660),
+                (Dot, 720, 780)
+            ]
+        );
+    }
+
+    #[test]
+    fn dit_memory_sends_tapped_dit_after_dash() {
+        let presses = [
+            (0, Paddle::Dah, true),
+            (50, Paddle::Dit, true),
+            (80, Paddle::Dit, false),
+            (100, Paddle::Dah, false),
+        ];
+        let elements = send(keyer(KeyerMode::IambicA), &presses);
+        assert_eq!(elements, [(Dash, 0, 180)]);
+        let elements = send(keyer(KeyerMode::IambicA).with_memory(true, false), &presses);
+        assert_eq!(elements, [(Dash, 0, 180), (Dot, 240, 300)]);
+    }
+
+    #[test]
+    fn dah_memory_sends_tapped_dah_after_dot() {
+        let presses = [
+            (0, Paddle::Dit, true),
+            (20, Paddle::Dah, true),
+            (30, Paddle::Dah, false),
+            (40, Paddle::Dit, false),
+        ];
+        let elements = send(keyer(KeyerMode::IambicA).with_memory(false, true), &presses);
+        assert_eq!(elements, [(Dot, 0, 60), (Dash, 120, 300)]);
+    }
+
+    #[test]
+    fn bug_repeats_dots_and_ignores_dah() {
+        let presses = [
+            (0, Paddle::Dit, true),
+            (100, Paddle::Dah, true),
+            (150, Paddle::Dah, false),
+            (250, Paddle::Dit, false),
+        ];
+        let elements = send(keyer(KeyerMode::Bug), &presses);
+        assert_eq!(elements, [(Dot, 0, 60), (Dot, 120, 180), (Dot, 240, 300)]);
+    }
+}
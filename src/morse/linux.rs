@@ -0,0 +1,554 @@
+use super::source::{EventKeySource, RawKeyEvent};
+use super::{KeyCode, KeySource, OutputSink};
+use evdev::uinput::{VirtualDevice, VirtualDeviceBuilder};
+use evdev::{AttributeSet, Device, EventType, InputEvent, InputEventKind, Key};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+/// older event timestamps mean that wall clock was stepped
+const MAX_EVENT_AGE: Duration = Duration::from_secs(1);
+
+/// Key state source backed by evdev devices (`/dev/input/event*`).
+///
+/// Reading requires access to the device nodes (usually membership in the `input` group).
+pub struct EvdevKeyboard {
+    devices: Vec<Device>,
+}
+
+impl EvdevKeyboard {
+    /// opens device at `path` or, if `path` is none, every device that reports key events
+    pub fn open(path: Option<&Path>) -> io::Result<Self> {
+        let devices = open_devices(path)?;
+        Ok(EvdevKeyboard { devices })
+    }
+}
+
+fn open_devices(path: Option<&Path>) -> io::Result<Vec<Device>> {
+    let devices = match path {
+        Some(path) => vec![Device::open(path)?],
+        None => evdev::enumerate()
+            .map(|(_, device)| device)
+            .filter(|device| {
+                device
+                    .supported_keys()
+                    .is_some_and(|keys| keys.contains(Key::KEY_SPACE))
+            })
+            .collect::<Vec<_>>(),
+    };
+    if devices.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "no readable keyboard found in /dev/input",
+        ));
+    }
+    Ok(devices)
+}
+
+impl KeySource for EvdevKeyboard {
+    /// true if key is held on any of opened devices
+    fn is_key_down(&mut self, key: enigo::Key) -> bool {
+        let evdev_keys = evdev_keys(key);
+        self.devices
+            .iter()
+            .any(|device| match device.get_key_state() {
+                Ok(state) => evdev_keys.iter().any(|&key| state.contains(key)),
+                // device was unplugged or became unreadable
+                Err(_) => false,
+            })
+    }
+
+    /// error of last device once none of them is readable
+    fn check(&mut self) -> io::Result<()> {
+        let mut last_error = None;
+        for device in &self.devices {
+            match device.get_key_state() {
+                Ok(_) => return Ok(()),
+                Err(err) => last_error = Some(err),
+            }
+        }
+        last_error.map_or(Ok(()), Err)
+    }
+}
+
+/// Opens devices like `EvdevKeyboard::open` and reads their key events in blocking threads,
+/// one per device, so keys are timed by kernel instead of polling.
+pub fn open_event_keyboard(path: Option<&Path>) -> io::Result<EventKeySource<Key>> {
+    let devices = open_devices(path)?;
+    let (sender, receiver) = mpsc::channel();
+    for mut device in devices {
+        let sender = sender.clone();
+        thread::spawn(move || {
+            if let Err(err) = read_key_events(&mut device, &sender) {
+                let _ = sender.send(Err(err));
+            }
+        });
+    }
+    Ok(EventKeySource::new(receiver, evdev_keys))
+}
+
+/// sends key presses and releases until device fails or receiver is dropped
+fn read_key_events(
+    device: &mut Device,
+    sender: &Sender<io::Result<RawKeyEvent<Key>>>,
+) -> io::Result<()> {
+    loop {
+        for event in device.fetch_events()? {
+            let InputEventKind::Key(key) = event.kind() else {
+                continue;
+            };
+            // 2 is autorepeat
+            let is_down = match event.value() {
+                0 => false,
+                1 => true,
+                _ => continue,
+            };
+            if sender
+                .send(Ok((key, is_down, event_instant(event.timestamp()))))
+                .is_err()
+            {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Kernel timestamps are wall-clock ones, they are turned into monotonic by their age.
+fn event_instant(timestamp: SystemTime) -> Instant {
+    let now = Instant::now();
+    match SystemTime::now().duration_since(timestamp) {
+        Ok(age) if age <= MAX_EVENT_AGE => now.checked_sub(age).unwrap_or(now),
+        _ => now,
+    }
+}
+
+/// evdev keys pressing `key`, generic modifiers match both left and right keys
+/// like VK_SHIFT and others do
+fn evdev_keys(key: enigo::Key) -> Vec<Key> {
+    let right_hand_key = match key {
+        enigo::Key::Shift => Some(Key::KEY_RIGHTSHIFT),
+        enigo::Key::Control => Some(Key::KEY_RIGHTCTRL),
+        enigo::Key::Alt => Some(Key::KEY_RIGHTALT),
+        #[allow(deprecated)]
+        enigo::Key::Meta | enigo::Key::Super | enigo::Key::Command | enigo::Key::Windows => {
+            Some(Key::KEY_RIGHTMETA)
+        }
+        _ => None,
+    };
+    [key_code_into_evdev_key(key)]
+        .into_iter()
+        .chain(right_hand_key)
+        .collect()
+}
+
+/// Virtual keyboard created through `/dev/uinput`.
+///
+/// Keystrokes are injected below display server, so output works on Wayland too.
+/// Characters are typed by their key position: latin ones on US layout, cyrillic ones
+/// on russian ЙЦУКЕН layout, so system layout must match current morse lang.
+pub struct UinputSink {
+    device: VirtualDevice,
+}
+
+impl UinputSink {
+    pub fn new() -> io::Result<Self> {
+        // every regular keyboard key
+        let keys = (Key::KEY_ESC.code()..=Key::KEY_MICMUTE.code())
+            .map(Key::new)
+            .collect::<AttributeSet<_>>();
+        let device = VirtualDeviceBuilder::new()?
+            .name("morse virtual keyboard")
+            .with_keys(&keys)?
+            .build()?;
+        Ok(UinputSink { device })
+    }
+
+    /// `/dev/input/event*` nodes of virtual keyboard, e.g. to read it back with `EvdevKeyboard`
+    pub fn dev_nodes(&mut self) -> io::Result<Vec<PathBuf>> {
+        self.device.enumerate_dev_nodes_blocking()?.collect()
+    }
+
+    fn click(&mut self, key: Key, with_shift: bool) -> io::Result<()> {
+        if with_shift {
+            self.emit_key(Key::KEY_LEFTSHIFT, true)?;
+        }
+        self.emit_key(key, true)?;
+        self.emit_key(key, false)?;
+        if with_shift {
+            self.emit_key(Key::KEY_LEFTSHIFT, false)?;
+        }
+        Ok(())
+    }
+
+    fn emit_key(&mut self, key: Key, is_down: bool) -> io::Result<()> {
+        self.device
+            .emit(&[InputEvent::new(EventType::KEY, key.code(), is_down as i32)])
+    }
+}
+
+impl OutputSink for UinputSink {
+    /// characters without key on supported layouts are skipped
+    fn type_text(&mut self, text: &str) {
+        for character in text.chars() {
+            if let Some((key, with_shift)) = char_into_key_stroke(character) {
+                let _ = self.click(key, with_shift);
+            }
+        }
+    }
+
+    fn press_key(&mut self, key: KeyCode) {
+        if let Some((key, with_shift)) = key_code_into_key_stroke(key) {
+            let _ = self.click(key, with_shift);
+        }
+    }
+
+    fn press_shifted_key(&mut self, key: KeyCode) {
+        if let Some((key, _)) = key_code_into_key_stroke(key) {
+            let _ = self.click(key, true);
+        }
+    }
+
+    fn erase(&mut self, count: usize) {
+        for _ in 0..count {
+            let _ = self.click(Key::KEY_BACKSPACE, false);
+        }
+    }
+
+    fn echoes_main_key(&self) -> bool {
+        true
+    }
+}
+
+/// layout keys are typed like `type_text` does, so cyrillic ones are not lost
+fn key_code_into_key_stroke(key_code: KeyCode) -> Option<(Key, bool)> {
+    match key_code {
+        KeyCode::Layout(character) => char_into_key_stroke(character),
+        key_code => {
+            let key = key_code_into_evdev_key(key_code);
+            (key != Key::KEY_UNKNOWN).then_some((key, false))
+        }
+    }
+}
+
+// linux counterpart of key_code_into_virtual_key
+pub fn key_code_into_evdev_key(key_code: enigo::Key) -> Key {
+    use enigo::Key as K;
+
+    match key_code {
+        K::Alt => Key::KEY_LEFTALT,
+        K::Backspace => Key::KEY_BACKSPACE,
+        K::Begin => Key::KEY_HOME,
+        K::Break => Key::KEY_PAUSE,
+        K::Cancel => Key::KEY_CANCEL,
+        K::CapsLock => Key::KEY_CAPSLOCK,
+        K::Clear => Key::KEY_CLEAR,
+        K::Control => Key::KEY_LEFTCTRL,
+        K::Delete => Key::KEY_DELETE,
+        K::DownArrow => Key::KEY_DOWN,
+        K::End => Key::KEY_END,
+        K::Escape => Key::KEY_ESC,
+        K::Execute => Key::KEY_UNKNOWN,
+        K::F1 => Key::KEY_F1,
+        K::F2 => Key::KEY_F2,
+        K::F3 => Key::KEY_F3,
+        K::F4 => Key::KEY_F4,
+        K::F5 => Key::KEY_F5,
+        K::F6 => Key::KEY_F6,
+        K::F7 => Key::KEY_F7,
+        K::F8 => Key::KEY_F8,
+        K::F9 => Key::KEY_F9,
+        K::F10 => Key::KEY_F10,
+        K::F11 => Key::KEY_F11,
+        K::F12 => Key::KEY_F12,
+        K::F13 => Key::KEY_F13,
+        K::F14 => Key::KEY_F14,
+        K::F15 => Key::KEY_F15,
+        K::F16 => Key::KEY_F16,
+        K::F17 => Key::KEY_F17,
+        K::F18 => Key::KEY_F18,
+        K::F19 => Key::KEY_F19,
+        K::F20 => Key::KEY_F20,
+        K::F21 => Key::KEY_F21,
+        K::F22 => Key::KEY_F22,
+        K::F23 => Key::KEY_F23,
+        K::F24 => Key::KEY_F24,
+        // evdev has no keys above F24
+        K::F25
+        | K::F26
+        | K::F27
+        | K::F28
+        | K::F29
+        | K::F30
+        | K::F31
+        | K::F32
+        | K::F33
+        | K::F34
+        | K::F35 => Key::KEY_UNKNOWN,
+        K::Find => Key::KEY_FIND,
+        K::Hangul => Key::KEY_HANGEUL,
+        K::Hanja => Key::KEY_HANJA,
+        K::Help => Key::KEY_HELP,
+        K::Home => Key::KEY_HOME,
+        K::Insert => Key::KEY_INSERT,
+        K::Kanji => Key::KEY_ZENKAKUHANKAKU,
+        K::LControl => Key::KEY_LEFTCTRL,
+        K::LeftArrow => Key::KEY_LEFT,
+        K::Linefeed => Key::KEY_LINEFEED,
+        K::LMenu => Key::KEY_LEFTALT,
+        K::LShift => Key::KEY_LEFTSHIFT,
+        K::ModeChange => Key::KEY_RIGHTALT,
+        K::Numlock => Key::KEY_NUMLOCK,
+        K::Option => Key::KEY_LEFTALT,
+        K::PageDown => Key::KEY_PAGEDOWN,
+        K::PageUp => Key::KEY_PAGEUP,
+        K::Pause => Key::KEY_PAUSE,
+        K::Print => Key::KEY_PRINT,
+        K::RControl => Key::KEY_RIGHTCTRL,
+        K::Redo => Key::KEY_REDO,
+        K::Return => Key::KEY_ENTER,
+        K::RightArrow => Key::KEY_RIGHT,
+        K::RShift => Key::KEY_RIGHTSHIFT,
+        K::ScrollLock => Key::KEY_SCROLLLOCK,
+        K::Select => Key::KEY_SELECT,
+        K::ScriptSwitch => Key::KEY_RIGHTALT,
+        K::Shift => Key::KEY_LEFTSHIFT,
+        K::ShiftLock => Key::KEY_CAPSLOCK,
+        K::Space => Key::KEY_SPACE,
+        K::SysReq => Key::KEY_SYSRQ,
+        K::Tab => Key::KEY_TAB,
+        K::Undo => Key::KEY_UNDO,
+        K::UpArrow => Key::KEY_UP,
+        K::MediaNextTrack => Key::KEY_NEXTSONG,
+        K::MediaPlayPause => Key::KEY_PLAYPAUSE,
+        K::MediaPrevTrack => Key::KEY_PREVIOUSSONG,
+        K::MediaStop => Key::KEY_STOPCD,
+        K::VolumeDown => Key::KEY_VOLUMEDOWN,
+        K::VolumeMute => Key::KEY_MUTE,
+        K::VolumeUp => Key::KEY_VOLUMEUP,
+        K::Layout(character) => layout_into_evdev_key(character),
+        K::Raw(raw_keycode) => Key::new(raw_keycode),
+        #[allow(deprecated)]
+        K::Meta | K::Super | K::Command | K::Windows => Key::KEY_LEFTMETA,
+        // keys added by later enigo versions
+        #[allow(unreachable_patterns)]
+        _ => Key::KEY_UNKNOWN,
+    }
+}
+
+/// physical key of character on US layout, `KEY_UNKNOWN` if there is none
+fn layout_into_evdev_key(character: char) -> Key {
+    match character.to_ascii_lowercase() {
+        'a' => Key::KEY_A,
+        'b' => Key::KEY_B,
+        'c' => Key::KEY_C,
+        'd' => Key::KEY_D,
+        'e' => Key::KEY_E,
+        'f' => Key::KEY_F,
+        'g' => Key::KEY_G,
+        'h' => Key::KEY_H,
+        'i' => Key::KEY_I,
+        'j' => Key::KEY_J,
+        'k' => Key::KEY_K,
+        'l' => Key::KEY_L,
+        'm' => Key::KEY_M,
+        'n' => Key::KEY_N,
+        'o' => Key::KEY_O,
+        'p' => Key::KEY_P,
+        'q' => Key::KEY_Q,
+        'r' => Key::KEY_R,
+        's' => Key::KEY_S,
+        't' => Key::KEY_T,
+        'u' => Key::KEY_U,
+        'v' => Key::KEY_V,
+        'w' => Key::KEY_W,
+        'x' => Key::KEY_X,
+        'y' => Key::KEY_Y,
+        'z' => Key::KEY_Z,
+        '0' => Key::KEY_0,
+        '1' => Key::KEY_1,
+        '2' => Key::KEY_2,
+        '3' => Key::KEY_3,
+        '4' => Key::KEY_4,
+        '5' => Key::KEY_5,
+        '6' => Key::KEY_6,
+        '7' => Key::KEY_7,
+        '8' => Key::KEY_8,
+        '9' => Key::KEY_9,
+        ' ' => Key::KEY_SPACE,
+        '-' => Key::KEY_MINUS,
+        '=' => Key::KEY_EQUAL,
+        '[' => Key::KEY_LEFTBRACE,
+        ']' => Key::KEY_RIGHTBRACE,
+        ';' => Key::KEY_SEMICOLON,
+        '\'' => Key::KEY_APOSTROPHE,
+        '`' => Key::KEY_GRAVE,
+        '\\' => Key::KEY_BACKSLASH,
+        ',' => Key::KEY_COMMA,
+        '.' => Key::KEY_DOT,
+        '/' => Key::KEY_SLASH,
+        _ => Key::KEY_UNKNOWN,
+    }
+}
+
+/// key and shift state typing `character`, none if there is no such key
+fn char_into_key_stroke(character: char) -> Option<(Key, bool)> {
+    let lower = character.to_lowercase().next()?;
+    let is_upper = lower != character;
+    let (us_character, is_shifted) = match lower {
+        // russian ЙЦУКЕН layout
+        'й' => ('q', false),
+        'ц' => ('w', false),
+        'у' => ('e', false),
+        'к' => ('r', false),
+        'е' => ('t', false),
+        'н' => ('y', false),
+        'г' => ('u', false),
+        'ш' => ('i', false),
+        'щ' => ('o', false),
+        'з' => ('p', false),
+        'х' => ('[', false),
+        'ъ' => (']', false),
+        'ф' => ('a', false),
+        'ы' => ('s', false),
+        'в' => ('d', false),
+        'а' => ('f', false),
+        'п' => ('g', false),
+        'р' => ('h', false),
+        'о' => ('j', false),
+        'л' => ('k', false),
+        'д' => ('l', false),
+        'ж' => (';', false),
+        'э' => ('\'', false),
+        'я' => ('z', false),
+        'ч' => ('x', false),
+        'с' => ('c', false),
+        'м' => ('v', false),
+        'и' => ('b', false),
+        'т' => ('n', false),
+        'ь' => ('m', false),
+        'б' => (',', false),
+        'ю' => ('.', false),
+        'ё' => ('`', false),
+        // shifted characters of US layout
+        '!' => ('1', true),
+        '@' => ('2', true),
+        '#' => ('3', true),
+        '$' => ('4', true),
+        '%' => ('5', true),
+        '^' => ('6', true),
+        '&' => ('7', true),
+        '*' => ('8', true),
+        '(' => ('9', true),
+        ')' => ('0', true),
+        '_' => ('-', true),
+        '+' => ('=', true),
+        '{' => ('[', true),
+        '}' => (']', true),
+        ':' => (';', true),
+        '"' => ('\'', true),
+        '~' => ('`', true),
+        '|' => ('\\', true),
+        '<' => (',', true),
+        '>' => ('.', true),
+        '?' => ('/', true),
+        other => (other, false),
+    };
+    let key = match us_character {
+        '\n' => Key::KEY_ENTER,
+        '\t' => Key::KEY_TAB,
+        other => layout_into_evdev_key(other),
+    };
+    (key != Key::KEY_UNKNOWN).then_some((key, is_upper || is_shifted))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::morse::{ConfigKey, ConfigLayoutKey, InputEventKey};
+    use std::time::Duration;
+
+    /// next `count` key events of `device` as (key, is_down), blocking until they come
+    fn read_keys(device: &mut Device, count: usize) -> Vec<(Key, bool)> {
+        let mut keys = Vec::new();
+        while keys.len() < count {
+            for event in device.fetch_events().unwrap() {
+                if let InputEventKind::Key(key) = event.kind() {
+                    keys.push((key, event.value() != 0));
+                }
+            }
+        }
+        keys
+    }
+
+    fn clicked(keys: &[Key]) -> Vec<(Key, bool)> {
+        let downs = keys.iter().map(|&key| (key, true));
+        let ups = keys.iter().rev().map(|&key| (key, false));
+        downs.chain(ups).collect()
+    }
+
+    fn layout_key(character: char) -> ConfigLayoutKey {
+        ConfigLayoutKey {
+            lower: KeyCode::Layout(character),
+        }
+    }
+
+    #[test]
+    #[ignore = "needs write access to /dev/uinput and read access to /dev/input"]
+    fn evdev_keyboard_reads_uinput_sink() {
+        let mut sink = UinputSink::new().unwrap();
+        // device nodes appear once udev handles new device
+        thread::sleep(Duration::from_millis(500));
+        let path = sink
+            .dev_nodes()
+            .unwrap()
+            .into_iter()
+            .find(|path| {
+                path.file_name()
+                    .is_some_and(|name| name.to_string_lossy().starts_with("event"))
+            })
+            .expect("no event node of virtual keyboard");
+        let mut keyboard = EvdevKeyboard::open(Some(&path)).unwrap();
+        let key = enigo::Key::Raw(Key::KEY_F24.code());
+
+        assert!(!keyboard.is_key_down(key));
+        sink.emit_key(Key::KEY_F24, true).unwrap();
+        thread::sleep(Duration::from_millis(50));
+        assert!(keyboard.is_key_down(key));
+        sink.emit_key(Key::KEY_F24, false).unwrap();
+        thread::sleep(Duration::from_millis(50));
+        assert!(!keyboard.is_key_down(key));
+
+        let mut device = Device::open(&path).unwrap();
+        let mut write_key = |key: ConfigKey, is_upper: bool| {
+            sink.write_key(&InputEventKey { key, is_upper });
+        };
+        write_key(ConfigKey::Code(KeyCode::Return), false);
+        write_key(ConfigKey::Layout(layout_key('б')), false);
+        write_key(ConfigKey::Layout(layout_key('б')), true);
+        write_key(
+            ConfigKey::Layout(ConfigLayoutKey {
+                lower: KeyCode::Tab,
+            }),
+            true,
+        );
+        write_key(
+            ConfigKey::Sequence(vec![layout_key('h'), layout_key('i')]),
+            true,
+        );
+        sink.press_key(KeyCode::Layout('б'));
+
+        let expected = [
+            clicked(&[Key::KEY_ENTER]),
+            clicked(&[Key::KEY_COMMA]),
+            clicked(&[Key::KEY_LEFTSHIFT, Key::KEY_COMMA]),
+            clicked(&[Key::KEY_LEFTSHIFT, Key::KEY_TAB]),
+            clicked(&[Key::KEY_LEFTSHIFT, Key::KEY_H]),
+            clicked(&[Key::KEY_LEFTSHIFT, Key::KEY_I]),
+            clicked(&[Key::KEY_COMMA]),
+        ]
+        .concat();
+        assert_eq!(read_keys(&mut device, expected.len()), expected);
+    }
+}
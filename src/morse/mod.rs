@@ -1,8 +1,50 @@
 use serde::{Deserialize, Serialize};
-use std::time::SystemTime;
-use std::{collections::HashMap, time::Duration};
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+#[cfg(target_os = "windows")]
 use windows::Win32::UI::Input::KeyboardAndMouse::VIRTUAL_KEY;
 
+pub mod audio;
+pub mod classifier;
+pub mod clock;
+pub mod cw;
+pub mod decode;
+pub mod decoder;
+pub mod discover;
+pub mod encode;
+pub mod error;
+pub mod keyer;
+#[cfg(target_os = "linux")]
+pub mod linux;
+pub mod output;
+pub mod record;
+pub mod reload;
+pub mod sidetone;
+pub mod source;
+pub mod timing;
+pub mod validate;
+#[cfg(target_os = "windows")]
+pub mod windows_hook;
+
+pub use audio::Tone;
+pub use classifier::{Gap, PressClassifier};
+pub use clock::{Clock, FakeClock, SystemClock};
+pub use decode::{DecodeError, DecodedText, UnknownSequence};
+pub use decoder::{Decoder, DecoderInput};
+pub use discover::ConfigSource;
+pub use encode::EncodeError;
+pub use error::{ConfigError, ConfigLocation};
+pub use keyer::{Keyer, KeyerElement, KeyerMode, Paddle};
+pub use output::OutputSink;
+pub use record::{RecordedKey, RecordingKeySource, ReplayKeySource};
+pub use reload::{ConfigOverrides, ConfigWatcher, ReloadError};
+pub use sidetone::{Sidetone, SidetoneSink};
+pub use source::{ChannelKeySource, EventKeySource, KeyEvent, KeySource};
+pub use timing::Timing;
+pub use validate::{ConfigIssue, Severity};
+
 pub type KeyCode = enigo::Key;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -34,10 +76,36 @@ pub struct ConfigSerde {
     pub pause: KeyCode,
     pub change_lang: KeyCode,
     pub change_case: KeyCode,
-    pub time_to_long_press: Duration,
+    /// `Straight` if not set
+    pub keyer: Option<KeyerMode>,
+    /// paddles of `keyer` modes other than `Straight`, which do not use `main`
+    pub dit: Option<KeyCode>,
+    pub dah: Option<KeyCode>,
+    /// paddle keyers send `dit` tapped during element after it, false if not set
+    pub dit_memory: Option<bool>,
+    /// same as `dit_memory` for `dah`
+    pub dah_memory: Option<bool>,
+    /// initial threshold between dot and dash, then it adapts to operator speed,
+    /// required if `timing` is not set
+    pub time_to_long_press: Option<Duration>,
+    /// keep `time_to_long_press` instead of adapting it, false if not set
+    pub lock_long_press: Option<bool>,
     pub listen_delay: Duration,
-    /// time to wait for transform sequence to event
-    pub accept_sequence_delay: Duration,
+    /// max time to wait for transform sequence to event,
+    /// sequence is accepted earlier after character gap (2 dot units of operator speed),
+    /// required if `timing` is not set
+    pub accept_sequence_delay: Option<Duration>,
+    /// wpm to derive `time_to_long_press` and `accept_sequence_delay` from, replaces them if set
+    pub timing: Option<Timing>,
+    /// typed after word gap (5 dot units of operator speed), space if not set
+    pub word_separator: Option<ConfigKey>,
+    /// generated audio, 600 Hz at half volume with 5 ms ramps if not set
+    pub tone: Option<Tone>,
+    /// linux only: evdev device to read keys from (e.g. `/dev/input/event3`),
+    /// all keyboards are read if not set
+    pub input_device: Option<PathBuf>,
+    /// lang on start, any of `langs` if not set
+    pub lang: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -51,32 +119,139 @@ pub struct Config {
     pub pause: KeyCode,
     pub change_lang: KeyCode,
     pub change_case: KeyCode,
+    pub keyer: KeyerMode,
+    /// set if `keyer` is a paddle one
+    pub dit: Option<KeyCode>,
+    pub dah: Option<KeyCode>,
+    pub dit_memory: bool,
+    pub dah_memory: bool,
     pub time_to_long_press: Duration,
+    pub lock_long_press: bool,
     pub listen_delay: Duration,
     pub accept_sequence_delay: Duration,
+    /// set only if config is expressed in wpm, see `Config::timing`
+    pub timing: Option<Timing>,
+    pub word_separator: ConfigKey,
+    pub tone: Tone,
+    pub input_device: Option<PathBuf>,
+    pub lang: Option<String>,
 }
 
-fn morse_seq_from_string(s: &str) -> Option<MorseSequence> {
+/// returns first character that is not `.` or `-` on error
+pub fn morse_seq_from_string(s: &str) -> Result<MorseSequence, char> {
     let mut sequence = Vec::new();
     for c in s.chars() {
         match c {
             '.' => sequence.push(MorseKey::Dot),
             '-' => sequence.push(MorseKey::Dash),
-            _ => return None,
+            _ => return Err(c),
         }
     }
-    Some(sequence)
+    Ok(sequence)
+}
+
+/// inverse of `morse_seq_from_string`
+pub fn morse_seq_to_string(sequence: &[MorseKey]) -> String {
+    sequence
+        .iter()
+        .map(|key| match key {
+            MorseKey::Dot => '.',
+            MorseKey::Dash => '-',
+        })
+        .collect()
+}
+
+impl Config {
+    /// key whose hold duration decides dot or dash, none for fully automatic keyers
+    pub fn manual_key(&self) -> Option<KeyCode> {
+        match self.keyer {
+            KeyerMode::Straight => Some(self.main),
+            KeyerMode::Bug => self.dah,
+            _ => None,
+        }
+    }
+
+    /// main, control and paddle keys
+    pub fn listened_keys(&self) -> Vec<KeyCode> {
+        [
+            self.main,
+            self.change_lang,
+            self.change_case,
+            self.pause,
+            self.exit,
+        ]
+        .into_iter()
+        .chain(self.dit)
+        .chain(self.dah)
+        .collect()
+    }
+
+    /// Key of sequence: functional sequences are looked up first, then ones of `lang`.
+    pub fn lookup(&self, lang: Option<&str>, sequence: &MorseSequence) -> Option<&ConfigKey> {
+        self.functional.get(sequence).or_else(|| {
+            lang.and_then(|lang| self.langs.get(lang))
+                .and_then(|keys| keys.get(sequence))
+        })
+    }
+
+    /// `timing` of config, derived from durations if it is not set
+    pub fn timing(&self) -> Timing {
+        self.timing.unwrap_or_else(|| {
+            Timing::from_durations(self.time_to_long_press, self.accept_sequence_delay)
+        })
+    }
+
+    /// `lang` if it is defined, first of `langs` in sorted order otherwise
+    pub fn start_lang(&self) -> Option<&String> {
+        self.lang
+            .as_ref()
+            .filter(|lang| self.langs.contains_key(*lang))
+            .or_else(|| self.langs.keys().min())
+    }
+
+    /// parses toml source, errors are located in it where possible
+    pub fn from_toml(source: &str) -> Result<Config, ConfigError> {
+        let config = toml::from_str::<ConfigSerde>(source)?;
+        config
+            .try_into()
+            .map_err(|err: ConfigError| err.locate(source))
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Config, ConfigError> {
+        Config::from_toml(&std::fs::read_to_string(path)?)
+    }
+}
+
+/// zero threshold or delay would make every timing derived from them zero
+pub(crate) fn check_durations(
+    time_to_long_press: Duration,
+    accept_sequence_delay: Duration,
+) -> Result<(), ConfigError> {
+    if time_to_long_press.is_zero() {
+        return Err(ConfigError::ZeroDuration("time_to_long_press"));
+    }
+    if accept_sequence_delay.is_zero() {
+        return Err(ConfigError::ZeroDuration("accept_sequence_delay"));
+    }
+    Ok(())
 }
 
 impl TryInto<Config> for ConfigSerde {
-    type Error = ();
+    type Error = ConfigError;
 
     fn try_into(self) -> Result<Config, Self::Error> {
         let mut langs = HashMap::new();
         for (lang, keys) in self.langs {
             let mut keys_map = HashMap::new();
             for (seq_serde, mut key) in keys {
-                let seq = morse_seq_from_string(&seq_serde).ok_or(())?;
+                let seq = morse_seq_from_string(&seq_serde).map_err(|character| {
+                    ConfigError::InvalidSequence {
+                        lang: Some(lang.clone()),
+                        sequence: seq_serde.clone(),
+                        character,
+                        location: None,
+                    }
+                })?;
                 if let ConfigKey::Layout(ref mut layout) = key {
                     if let enigo::Key::Layout(ref mut layout) = layout.lower {
                         *layout = layout
@@ -91,9 +266,36 @@ impl TryInto<Config> for ConfigSerde {
         }
         let mut functional = HashMap::new();
         for (seq_serde, key) in self.functional {
-            let seq = morse_seq_from_string(&seq_serde).ok_or(())?;
+            let seq = morse_seq_from_string(&seq_serde).map_err(|character| {
+                ConfigError::InvalidSequence {
+                    lang: None,
+                    sequence: seq_serde.clone(),
+                    character,
+                    location: None,
+                }
+            })?;
             functional.insert(seq, key);
         }
+
+        let (time_to_long_press, accept_sequence_delay) = match self.timing {
+            Some(timing) if !timing.is_valid() => return Err(ConfigError::InvalidTiming(timing)),
+            Some(timing) => (timing.time_to_long_press(), timing.accept_sequence_delay()),
+            None => (
+                self.time_to_long_press
+                    .ok_or(ConfigError::MissingField("time_to_long_press"))?,
+                self.accept_sequence_delay
+                    .ok_or(ConfigError::MissingField("accept_sequence_delay"))?,
+            ),
+        };
+        check_durations(time_to_long_press, accept_sequence_delay)?;
+        let tone = self.tone.unwrap_or_default();
+        if !tone.is_valid() {
+            return Err(ConfigError::InvalidTone(tone));
+        }
+        let keyer = self.keyer.unwrap_or_default();
+        if keyer.is_paddle() && (self.dit.is_none() || self.dah.is_none()) {
+            return Err(ConfigError::MissingPaddles(keyer));
+        }
         Ok(Config {
             langs,
             functional,
@@ -102,16 +304,29 @@ impl TryInto<Config> for ConfigSerde {
             pause: self.pause,
             change_lang: self.change_lang,
             change_case: self.change_case,
-            time_to_long_press: self.time_to_long_press,
+            keyer,
+            dit: self.dit,
+            dah: self.dah,
+            dit_memory: self.dit_memory.unwrap_or(false),
+            dah_memory: self.dah_memory.unwrap_or(false),
+            time_to_long_press,
+            lock_long_press: self.lock_long_press.unwrap_or(false),
             listen_delay: self.listen_delay,
-            accept_sequence_delay: self.accept_sequence_delay,
+            accept_sequence_delay,
+            timing: self.timing,
+            word_separator: self
+                .word_separator
+                .unwrap_or(ConfigKey::Code(KeyCode::Space)),
+            tone,
+            input_device: self.input_device,
+            lang: self.lang,
         })
     }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum KeyState {
-    Down(std::time::SystemTime),
+    Down(Instant),
     NotPressed,
 }
 
@@ -130,11 +345,15 @@ pub struct InputState {
     pub change_lang_key_state: KeyState,
     pub pause_key_state: KeyState,
     pub exit_key_state: KeyState,
-    pub last_main_key_press: Option<SystemTime>,
+    pub last_main_key_press: Option<Instant>,
     pub sequence: MorseSequence,
     pub is_upper_case: bool,
     pub lang: Option<String>,
     pub work_state: InputWorkState,
+    /// dot and dash estimates, see `PressClassifier::estimated_wpm`
+    pub press_classifier: PressClassifier,
+    /// main key or keyer element is down, see `InputEvent::KeyingChange`
+    pub is_keyed: bool,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -151,9 +370,16 @@ pub struct InputEventKey {
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum InputEvent {
+    /// manual key or paddle is pressed while working, comes before other events of its input
+    KeyPress(KeyCode),
     MorseKey(MorseKey),
+    /// main key (or keyer element) goes down or up at this time, drives sidetone;
+    /// keyer elements go down once they start, before their `MorseKey`
+    KeyingChange(bool, Instant),
     SequenceParsed(MorseSequence, InputEventKey),
     SeqRejected(MorseSequence, SequenceRejectReason),
+    /// word gap after parsed sequence, contains `word_separator`
+    WordSeparator(InputEventKey),
     /// current lang
     LangChange(String),
     /// true - upper case, false - lower case
@@ -161,6 +387,10 @@ pub enum InputEvent {
     Exit,
     /// true - pause, false - unpause
     PauseToggle(bool),
+    /// config file is changed and new config is in use
+    ConfigReloaded,
+    /// config file is changed, but old config is kept, contains error message
+    ConfigReloadFailed(String),
 }
 
 impl InputState {
@@ -175,197 +405,145 @@ impl InputState {
             last_main_key_press: None,
             sequence: Vec::new(),
             is_upper_case: false,
-            lang: config.langs.keys().next().map(|s| s.clone()),
+            lang: config.start_lang().cloned(),
             work_state: InputWorkState::Work,
+            press_classifier: PressClassifier::new(config),
+            is_keyed: false,
         }
     }
 }
 
-/// uses global mutable state
-pub fn listen_loop(config: &Config, event_handler: fn(InputEvent, &mut InputState) -> ()) {
-    let mut state: InputState = InputState::new(config);
-
-    while state.work_state != InputWorkState::Exit {
-        // handle main key press
-        if state.work_state == InputWorkState::Work {
-            if let Some(main_key_hold_duration) =
-                key_hold_duration(config.main, state.main_key_state)
-            {
-                let morse_key = if main_key_hold_duration < config.time_to_long_press {
-                    MorseKey::Dot
-                } else {
-                    MorseKey::Dash
-                };
-                state.sequence.push(morse_key);
-                state.last_main_key_press = Some(SystemTime::now());
-
-                event_handler(InputEvent::MorseKey(morse_key), &mut state);
-            }
-        }
-
-        // handle other keys
+/// Decodes keys of `source` until exit, swapping in config of `watcher` once it changes.
+/// Polled keys and ticks are timed by `clock`.
+///
+/// Sources that can wait for events are waited for until next event, for `listen_delay`
+/// while sequence or word is pending and for config check interval otherwise.
+/// Others are polled every `listen_delay`.
+///
+/// Stops with error of `KeySource::check` if source can not report keys anymore.
+pub fn listen_loop<S, C, F>(
+    config: &Config,
+    mut watcher: Option<&mut ConfigWatcher>,
+    source: &mut S,
+    clock: &C,
+    mut event_handler: F,
+) -> io::Result<()>
+where
+    S: KeySource + ?Sized,
+    C: Clock + ?Sized,
+    F: FnMut(InputEvent, &mut InputState),
+{
+    let mut decoder = Decoder::new(config.clone());
+    let mut pressed_keys = HashSet::new();
+    let mut listened_keys = Vec::new();
+
+    while decoder.state().work_state != InputWorkState::Exit {
+        if let Some(result) = watcher
+            .as_deref_mut()
+            .and_then(|watcher| watcher.poll(clock.now()))
         {
-            if state.work_state == InputWorkState::Work {
-                if let Some(_) = key_hold_duration(config.change_lang, state.lang_key_state) {
-                    // if lang is None, then config not contains any lang
-                    if state.lang.is_some() {
-                        // cyclically find next lang in config.langs HashMap
-                        let curr_lang = state.lang.as_ref().unwrap().clone();
-                        let keys = config.langs.keys().collect::<Vec<_>>();
-                        let curr_lang_iter = keys
-                            .iter()
-                            .position(|s| *s == &curr_lang)
-                            .expect("current lang not found in config.langs");
-                        let next_lang = keys[(curr_lang_iter + 1) % keys.len()].clone();
-                        state.lang = Some(next_lang.clone());
-
-                        event_handler(InputEvent::LangChange { 0: next_lang }, &mut state);
+            let event = match result {
+                Ok(config) => {
+                    for event in decoder.replace_config(config, clock.now()) {
+                        event_handler(event, decoder.state_mut());
                     }
+                    InputEvent::ConfigReloaded
                 }
-            }
-
-            if state.work_state == InputWorkState::Work {
-                if let Some(_) = key_hold_duration(config.change_case, state.change_case_key_state)
-                {
-                    state.is_upper_case = !state.is_upper_case;
-
-                    event_handler(
-                        InputEvent::CaseChange {
-                            0: state.is_upper_case,
-                        },
-                        &mut state,
-                    );
-                }
-            }
-
-            if state.work_state != InputWorkState::Exit {
-                if let Some(_) = key_hold_duration(config.pause, state.pause_key_state) {
-                    state.work_state = match state.work_state {
-                        InputWorkState::Pause => InputWorkState::Work,
-                        InputWorkState::Work => InputWorkState::Pause,
-                        InputWorkState::Exit => InputWorkState::Exit,
-                    };
-
-                    event_handler(
-                        InputEvent::PauseToggle {
-                            0: state.work_state == InputWorkState::Pause,
-                        },
-                        &mut state,
-                    );
-                }
-            }
-
-            if let Some(_) = key_hold_duration(config.exit, state.exit_key_state) {
-                state.work_state = InputWorkState::Exit;
-
-                event_handler(InputEvent::Exit, &mut state);
-            }
+                Err(err) => InputEvent::ConfigReloadFailed(err.to_string()),
+            };
+            event_handler(event, decoder.state_mut());
         }
 
-        // handle morse sequence
-        // work_state is work && main key is up && sequence is not empty && last main key press was
-        // long enough ago
-        if state.work_state == InputWorkState::Work {
-            if let KeyState::NotPressed = state.main_key_state {
-                if let Some(true) = state.last_main_key_press.map(|t| {
-                    t.elapsed().unwrap() > config.accept_sequence_delay
-                        && !state.sequence.is_empty()
-                }) {
-                    if let Some(config_key) = config.functional.get(&state.sequence) {
-                        event_handler(
-                            InputEvent::SequenceParsed(
-                                state.sequence.clone(),
-                                InputEventKey {
-                                    key: config_key.clone(),
-                                    is_upper: state.is_upper_case,
-                                },
-                            ),
-                            &mut state,
-                        );
-                    } else {
-                        match config.langs.get(state.lang.as_ref().unwrap()) {
-                            Some(keys) => {
-                                if let Some(config_key) = keys.get(&state.sequence) {
-                                    event_handler(
-                                        InputEvent::SequenceParsed(
-                                            state.sequence.clone(),
-                                            InputEventKey {
-                                                key: config_key.clone(),
-                                                is_upper: state.is_upper_case,
-                                            },
-                                        ),
-                                        &mut state,
-                                    );
-                                } else {
-                                    event_handler(
-                                        InputEvent::SeqRejected(
-                                            state.sequence.clone(),
-                                            SequenceRejectReason::InvalidSequence,
-                                        ),
-                                        &mut state,
-                                    );
-                                }
-                            }
-                            None => {
-                                event_handler(
-                                    InputEvent::SeqRejected(
-                                        state.sequence.clone(),
-                                        SequenceRejectReason::NoLangsLoaded,
-                                    ),
-                                    &mut state,
-                                );
-                            }
-                        }
-                    }
-                    state.last_main_key_press = None;
-                    state.sequence.clear();
-                }
+        let config = decoder.config();
+        let keys = config.listened_keys();
+        if keys != listened_keys {
+            source.listen_keys(&keys);
+            listened_keys = keys.clone();
+        }
+        let listen_delay = config.listen_delay;
+        let key_events = source
+            .poll_events()
+            .unwrap_or_else(|| poll_key_events(source, &keys, &mut pressed_keys, clock.now()));
+
+        let inputs = key_events
+            .into_iter()
+            .map(DecoderInput::from)
+            .chain([DecoderInput::Tick(clock.now())]);
+        for input in inputs {
+            for event in decoder.handle(input) {
+                event_handler(event, decoder.state_mut());
             }
         }
-
-        // update key states
-        update_key_state(&mut state.main_key_state, config.main);
-        update_key_state(&mut state.lang_key_state, config.change_lang);
-        update_key_state(&mut state.change_case_key_state, config.change_case);
-        update_key_state(&mut state.pause_key_state, config.pause);
-        update_key_state(&mut state.exit_key_state, config.exit);
-
-        std::thread::sleep(config.listen_delay);
+        source.check()?;
+
+        let timeout = match decoder.is_idle() {
+            true => reload::CHECK_INTERVAL,
+            false => listen_delay,
+        };
+        if !source.wait_events(timeout) {
+            std::thread::sleep(listen_delay);
+        }
     }
+    Ok(())
 }
 
-fn update_key_state(state: &mut KeyState, key: enigo::Key) {
-    match (is_key_down(key), &state) {
-        (true, &KeyState::NotPressed) => {
-            *state = KeyState::Down(std::time::SystemTime::now());
-        }
-        (false, _) => {
-            *state = KeyState::NotPressed;
-        }
-        _ => {}
-    };
+/// queries every key of `keys` and reports ones changed since previous call at `time`
+pub(crate) fn poll_key_events<S: KeySource + ?Sized>(
+    source: &mut S,
+    keys: &[KeyCode],
+    pressed_keys: &mut HashSet<KeyCode>,
+    time: Instant,
+) -> Vec<KeyEvent> {
+    keys.iter()
+        .filter_map(|&key| {
+            let is_down = source.is_key_down(key);
+            let is_changed = if is_down {
+                pressed_keys.insert(key)
+            } else {
+                pressed_keys.remove(&key)
+            };
+            is_changed.then_some(KeyEvent { key, is_down, time })
+        })
+        .collect()
 }
 
-/// returns none if key is not pressed or previous state was not pressed
-fn key_hold_duration(key: enigo::Key, state: KeyState) -> Option<Duration> {
-    match (is_key_down(key), state) {
-        (false, KeyState::Down(time)) => Some(time.elapsed().unwrap()),
-        _ => None,
+/// global key state, polled with `GetAsyncKeyState`
+#[cfg(target_os = "windows")]
+pub struct AsyncKeyState;
+
+#[cfg(target_os = "windows")]
+impl KeySource for AsyncKeyState {
+    fn is_key_down(&mut self, key: KeyCode) -> bool {
+        use windows::Win32::UI::Input::KeyboardAndMouse::GetAsyncKeyState;
+
+        // get most significant bit of return value
+        // https://learn.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-getasynckeystate#return-value
+        unsafe { GetAsyncKeyState(key_code_into_virtual_key(key).0 as i32) < 0 }
     }
 }
 
+/// event-driven key source of current platform, keyboard hook
 #[cfg(target_os = "windows")]
-fn is_key_down(key: enigo::Key) -> bool {
-    use windows::Win32::UI::Input::KeyboardAndMouse::GetAsyncKeyState;
+pub fn system_key_source(_config: &Config) -> std::io::Result<EventKeySource<u16>> {
+    windows_hook::open_hook_keyboard()
+}
+
+/// polled key source of current platform
+#[cfg(target_os = "windows")]
+pub fn system_polling_key_source(_config: &Config) -> std::io::Result<AsyncKeyState> {
+    Ok(AsyncKeyState)
+}
 
-    // get most significant bit of return value
-    // https://learn.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-getasynckeystate#return-value
-    unsafe { GetAsyncKeyState(key_code_into_virtual_key(key).0 as i32) < 0 }
+/// event-driven key source of current platform, reads `config.input_device` if set
+#[cfg(target_os = "linux")]
+pub fn system_key_source(config: &Config) -> std::io::Result<EventKeySource<evdev::Key>> {
+    linux::open_event_keyboard(config.input_device.as_deref())
 }
 
-#[cfg(not(target_os = "windows"))]
-fn is_key_down(key: enigo::Key) -> bool {
-    unimplemented!()
+/// polled key source of current platform, reads `config.input_device` if set
+#[cfg(target_os = "linux")]
+pub fn system_polling_key_source(config: &Config) -> std::io::Result<linux::EvdevKeyboard> {
+    linux::EvdevKeyboard::open(config.input_device.as_deref())
 }
 
 // same as enigo::win::win_impl::key_to_keycode
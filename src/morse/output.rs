@@ -0,0 +1,395 @@
+use super::{ConfigKey, ConfigLayoutKey, InputEvent, InputEventKey, InputState, KeyCode, MorseKey};
+use enigo::KeyboardControllable;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+
+const DOT_KEY: &str = ".";
+const DOT_KEY_UPPER: &str = ">";
+const DASH_KEY: &str = "-";
+const DASH_KEY_UPPER: &str = "_";
+
+/// Target of decoded text.
+pub trait OutputSink {
+    fn type_text(&mut self, text: &str);
+
+    fn press_key(&mut self, key: KeyCode);
+
+    /// presses key while Shift is held, same as `press_key` for text sinks
+    fn press_shifted_key(&mut self, key: KeyCode) {
+        self.press_key(key);
+    }
+
+    /// removes `count` last characters
+    fn erase(&mut self, count: usize);
+
+    /// true if manual key and paddle presses themselves reach the output
+    /// (e.g. global keystrokes), so each of them must be erased
+    fn echoes_main_key(&self) -> bool {
+        false
+    }
+
+    /// Echoes morse keys as `.` and `-` (`>` and `_` in upper case), then replaces
+    /// them with decoded key once sequence is parsed, or removes them if it is rejected.
+    /// Keyer elements are echoed one by one, but paddle press is erased once.
+    fn write_event(&mut self, event: &InputEvent, state: &InputState) {
+        match event {
+            InputEvent::KeyPress(_) if self.echoes_main_key() => self.erase(1),
+            InputEvent::MorseKey(key) => {
+                let echo = match (key, state.is_upper_case) {
+                    (MorseKey::Dot, false) => DOT_KEY,
+                    (MorseKey::Dot, true) => DOT_KEY_UPPER,
+                    (MorseKey::Dash, false) => DASH_KEY,
+                    (MorseKey::Dash, true) => DASH_KEY_UPPER,
+                };
+                self.type_text(echo);
+            }
+            InputEvent::SequenceParsed(seq, event_key) => {
+                self.erase(seq.len());
+                self.write_key(event_key);
+            }
+            InputEvent::SeqRejected(seq, _) => {
+                self.erase(seq.len());
+            }
+            InputEvent::WordSeparator(event_key) => self.write_key(event_key),
+            _ => {}
+        }
+    }
+
+    fn write_key(&mut self, event_key: &InputEventKey) {
+        match &event_key.key {
+            ConfigKey::Code(code) => self.press_key(*code),
+            ConfigKey::Layout(layout) => self.write_layout_key(layout, event_key.is_upper),
+            ConfigKey::Sequence(seq) => {
+                for layout in seq {
+                    self.write_layout_key(layout, event_key.is_upper);
+                }
+            }
+        }
+    }
+
+    fn write_layout_key(&mut self, layout_key: &ConfigLayoutKey, is_upper: bool) {
+        match layout_key.lower {
+            KeyCode::Layout(layout) if is_upper => {
+                self.type_text(&layout.to_uppercase().to_string())
+            }
+            KeyCode::Layout(layout) => self.type_text(&layout.to_string()),
+            code if is_upper => self.press_shifted_key(code),
+            code => self.press_key(code),
+        }
+    }
+}
+
+/// text typed by key, none for keys without one (e.g. function keys)
+pub fn key_code_into_char(key: KeyCode) -> Option<char> {
+    match key {
+        KeyCode::Layout(layout) => Some(layout),
+        KeyCode::Space => Some(' '),
+        KeyCode::Return => Some('\n'),
+        KeyCode::Tab => Some('\t'),
+        _ => None,
+    }
+}
+
+/// text typed by config key in lower case, as written by text sinks
+pub fn config_key_text(key: &ConfigKey) -> String {
+    let mut buffer = TextBuffer::new();
+    buffer.write_key(&InputEventKey {
+        key: key.clone(),
+        is_upper: false,
+    });
+    buffer.text
+}
+
+/// Synthetic keystrokes sent to focused window.
+pub struct EnigoSink {
+    enigo: enigo::Enigo,
+}
+
+impl EnigoSink {
+    pub fn new() -> Self {
+        EnigoSink {
+            enigo: enigo::Enigo::new(),
+        }
+    }
+}
+
+impl Default for EnigoSink {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OutputSink for EnigoSink {
+    fn type_text(&mut self, text: &str) {
+        self.enigo.key_sequence(text);
+    }
+
+    fn press_key(&mut self, key: KeyCode) {
+        self.enigo.key_click(key);
+    }
+
+    fn press_shifted_key(&mut self, key: KeyCode) {
+        self.enigo.key_down(KeyCode::Shift);
+        self.enigo.key_click(key);
+        self.enigo.key_up(KeyCode::Shift);
+    }
+
+    fn erase(&mut self, count: usize) {
+        for _ in 0..count {
+            self.enigo.key_click(KeyCode::Backspace);
+        }
+    }
+
+    fn echoes_main_key(&self) -> bool {
+        true
+    }
+}
+
+/// In-memory text, keys without text are ignored.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TextBuffer {
+    pub text: String,
+}
+
+impl TextBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl OutputSink for TextBuffer {
+    fn type_text(&mut self, text: &str) {
+        self.text.push_str(text);
+    }
+
+    fn press_key(&mut self, key: KeyCode) {
+        match key {
+            KeyCode::Backspace => self.erase(1),
+            key => {
+                if let Some(character) = key_code_into_char(key) {
+                    self.text.push(character);
+                }
+            }
+        }
+    }
+
+    fn erase(&mut self, count: usize) {
+        for _ in 0..count {
+            self.text.pop();
+        }
+    }
+}
+
+/// Terminal output, erased characters are overwritten with backspace sequences.
+#[derive(Debug, Default)]
+pub struct StdoutSink;
+
+impl OutputSink for StdoutSink {
+    fn type_text(&mut self, text: &str) {
+        let mut stdout = io::stdout().lock();
+        let _ = stdout.write_all(text.as_bytes());
+        let _ = stdout.flush();
+    }
+
+    fn press_key(&mut self, key: KeyCode) {
+        match key {
+            KeyCode::Backspace => self.erase(1),
+            key => {
+                if let Some(character) = key_code_into_char(key) {
+                    self.type_text(&character.to_string());
+                }
+            }
+        }
+    }
+
+    fn erase(&mut self, count: usize) {
+        self.type_text(&"\u{8} \u{8}".repeat(count));
+    }
+}
+
+/// Appends text to file.
+///
+/// Only text written by this sink can be erased: file is truncated back to it.
+pub struct FileSink {
+    file: File,
+    /// text written since opening, used to find byte length of erased characters
+    written: String,
+}
+
+impl FileSink {
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(FileSink {
+            file,
+            written: String::new(),
+        })
+    }
+}
+
+impl OutputSink for FileSink {
+    fn type_text(&mut self, text: &str) {
+        if self.file.write_all(text.as_bytes()).is_ok() {
+            self.written.push_str(text);
+        }
+    }
+
+    fn press_key(&mut self, key: KeyCode) {
+        match key {
+            KeyCode::Backspace => self.erase(1),
+            key => {
+                if let Some(character) = key_code_into_char(key) {
+                    self.type_text(&character.to_string());
+                }
+            }
+        }
+    }
+
+    fn erase(&mut self, count: usize) {
+        let mut erased_len = 0;
+        for _ in 0..count {
+            match self.written.pop() {
+                Some(character) => erased_len += character.len_utf8(),
+                None => break,
+            }
+        }
+        if erased_len == 0 {
+            return;
+        }
+        if let Ok(metadata) = self.file.metadata() {
+            let _ = self
+                .file
+                .set_len(metadata.len().saturating_sub(erased_len as u64));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{OutputSink, TextBuffer};
+    use crate::morse::{
+        Config, ConfigKey, ConfigLayoutKey, Decoder, DecoderInput, InputEventKey, KeyCode,
+        KeyerMode, Timing,
+    };
+    use std::time::{Duration, Instant};
+
+    /// text of focused window that also receives typed keys, like one of `EnigoSink`
+    struct EchoingBuffer(TextBuffer);
+
+    impl OutputSink for EchoingBuffer {
+        fn type_text(&mut self, text: &str) {
+            self.0.type_text(text);
+        }
+
+        fn press_key(&mut self, key: KeyCode) {
+            self.0.press_key(key);
+        }
+
+        fn erase(&mut self, count: usize) {
+            self.0.erase(count);
+        }
+
+        fn echoes_main_key(&self) -> bool {
+            true
+        }
+    }
+
+    /// 60 ms unit, `z` and `x` paddles type their letters when pressed
+    fn paddle_config(keyer: KeyerMode) -> Config {
+        let timing = Timing::new(20.0);
+        let mut config = Config::default_config();
+        config.lang = Some("en".into());
+        config.keyer = keyer;
+        config.dit = Some(KeyCode::Layout('z'));
+        config.dah = Some(KeyCode::Layout('x'));
+        config.timing = Some(timing);
+        config.time_to_long_press = timing.time_to_long_press();
+        config.accept_sequence_delay = timing.accept_sequence_delay();
+        config
+    }
+
+    /// `(ms, key, is_down)` changes, every press types its key before it is decoded
+    fn type_keys(config: Config, keys: &[(u64, char, bool)]) -> String {
+        let start = Instant::now();
+        let mut decoder = Decoder::new(config);
+        let mut sink = EchoingBuffer(TextBuffer::new());
+        let inputs = keys.iter().map(|&(ms, key, is_down)| {
+            let time = start + Duration::from_millis(ms);
+            match is_down {
+                true => DecoderInput::KeyDown(KeyCode::Layout(key), time),
+                false => DecoderInput::KeyUp(KeyCode::Layout(key), time),
+            }
+        });
+        let end = start + Duration::from_secs(60);
+        for input in inputs.chain([DecoderInput::Tick(end)]) {
+            if let DecoderInput::KeyDown(key, _) = input {
+                sink.press_key(key);
+            }
+            for event in decoder.handle(input) {
+                sink.write_event(&event, decoder.state());
+            }
+        }
+        sink.0.text.trim_end().to_string()
+    }
+
+    #[test]
+    fn held_paddle_is_erased_once() {
+        // dit tap, then dah held for three dashes
+        let config = paddle_config(KeyerMode::IambicA);
+        let keys = [
+            (0, 'z', true),
+            (30, 'z', false),
+            (500, 'x', true),
+            (1100, 'x', false),
+        ];
+        assert_eq!(type_keys(config, &keys), "e o");
+    }
+
+    #[test]
+    fn bug_dots_and_manual_dash_are_erased_once() {
+        // dit tap, then three dots of held dit and manual dash
+        let config = paddle_config(KeyerMode::Bug);
+        let keys = [
+            (0, 'z', true),
+            (30, 'z', false),
+            (500, 'z', true),
+            (750, 'z', false),
+            (900, 'x', true),
+            (1080, 'x', false),
+        ];
+        assert_eq!(type_keys(config, &keys), "e v");
+    }
+
+    /// pressed keys with true if Shift was held
+    #[derive(Default)]
+    struct KeyLog(Vec<(KeyCode, bool)>);
+
+    impl OutputSink for KeyLog {
+        fn type_text(&mut self, _text: &str) {}
+
+        fn press_key(&mut self, key: KeyCode) {
+            self.0.push((key, false));
+        }
+
+        fn press_shifted_key(&mut self, key: KeyCode) {
+            self.0.push((key, true));
+        }
+
+        fn erase(&mut self, _count: usize) {}
+    }
+
+    #[test]
+    fn upper_case_key_code_is_shifted() {
+        let layout_key = ConfigLayoutKey {
+            lower: KeyCode::Tab,
+        };
+        let mut sink = KeyLog::default();
+        for is_upper in [false, true] {
+            sink.write_key(&InputEventKey {
+                key: ConfigKey::Layout(layout_key.clone()),
+                is_upper,
+            });
+        }
+        assert_eq!(sink.0, [(KeyCode::Tab, false), (KeyCode::Tab, true)]);
+    }
+}
@@ -0,0 +1,313 @@
+use super::decoder::decode_inputs;
+use super::{poll_key_events, Clock, Config, DecoderInput, KeyCode, KeyEvent, KeySource};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Key press or release of recording, one JSON object per line in files,
+/// e.g. `{"key":"Space","is_down":true,"time_us":1520000}`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecordedKey {
+    pub key: KeyCode,
+    pub is_down: bool,
+    /// microseconds since recording start, never decreases
+    pub time_us: u64,
+}
+
+impl RecordedKey {
+    pub fn time(&self) -> Duration {
+        Duration::from_micros(self.time_us)
+    }
+
+    pub fn to_key_event(&self, start: Instant) -> KeyEvent {
+        KeyEvent {
+            key: self.key,
+            is_down: self.is_down,
+            time: start + self.time(),
+        }
+    }
+}
+
+/// Passes keys of `source` through, writing them as JSON lines.
+///
+/// Sources that can only be queried are polled for listened keys.
+/// Recording stops on first write error, `check` then returns it.
+pub struct RecordingKeySource<S, W, C> {
+    source: S,
+    clock: C,
+    keys: Vec<KeyCode>,
+    pressed_keys: HashSet<KeyCode>,
+    writer: Option<W>,
+    error: Option<io::Error>,
+    start: Instant,
+    last_time: Duration,
+}
+
+impl<S: KeySource, W: Write, C: Clock> RecordingKeySource<S, W, C> {
+    /// recording starts now by `clock`, it also times polled keys
+    pub fn new(source: S, writer: W, clock: C) -> Self {
+        RecordingKeySource {
+            source,
+            keys: Vec::new(),
+            pressed_keys: HashSet::new(),
+            writer: Some(writer),
+            error: None,
+            start: clock.now(),
+            clock,
+            last_time: Duration::ZERO,
+        }
+    }
+
+    fn record(&mut self, events: &[KeyEvent]) -> io::Result<()> {
+        let Some(writer) = &mut self.writer else {
+            return Ok(());
+        };
+        for event in events {
+            // events of pushing sources may come out of order, recorded times must not
+            let time = event.time.saturating_duration_since(self.start);
+            self.last_time = self.last_time.max(time);
+            let recorded = RecordedKey {
+                key: event.key,
+                is_down: event.is_down,
+                time_us: self.last_time.as_micros() as u64,
+            };
+            serde_json::to_writer(&mut *writer, &recorded)?;
+            writer.write_all(b"\n")?;
+        }
+        writer.flush()
+    }
+}
+
+impl<S: KeySource, W: Write, C: Clock> KeySource for RecordingKeySource<S, W, C> {
+    fn is_key_down(&mut self, key: KeyCode) -> bool {
+        self.source.is_key_down(key)
+    }
+
+    fn poll_events(&mut self) -> Option<Vec<KeyEvent>> {
+        let events = self.source.poll_events().unwrap_or_else(|| {
+            let time = self.clock.now();
+            poll_key_events(&mut self.source, &self.keys, &mut self.pressed_keys, time)
+        });
+        if !events.is_empty() {
+            if let Err(err) = self.record(&events) {
+                self.error = Some(err);
+                self.writer = None;
+            }
+        }
+        Some(events)
+    }
+
+    fn wait_events(&mut self, timeout: Duration) -> bool {
+        self.source.wait_events(timeout)
+    }
+
+    fn listen_keys(&mut self, keys: &[KeyCode]) {
+        self.keys = keys.to_vec();
+        self.source.listen_keys(keys);
+    }
+
+    /// write error of recording, then errors of `source`
+    fn check(&mut self) -> io::Result<()> {
+        match self.error.take() {
+            Some(err) => Err(err),
+            None => self.source.check(),
+        }
+    }
+}
+
+/// Feeds recorded keys back in real time, recording starts at first poll.
+pub struct ReplayKeySource<C> {
+    clock: C,
+    keys: Vec<RecordedKey>,
+    next: usize,
+    start: Option<Instant>,
+    pressed_keys: HashSet<KeyCode>,
+}
+
+impl<C: Clock> ReplayKeySource<C> {
+    pub fn new(keys: Vec<RecordedKey>, clock: C) -> Self {
+        ReplayKeySource {
+            clock,
+            keys,
+            next: 0,
+            start: None,
+            pressed_keys: HashSet::new(),
+        }
+    }
+
+    /// true once every key is replayed
+    pub fn is_finished(&self) -> bool {
+        self.next >= self.keys.len()
+    }
+}
+
+impl<C: Clock> KeySource for ReplayKeySource<C> {
+    fn is_key_down(&mut self, key: KeyCode) -> bool {
+        self.pressed_keys.contains(&key)
+    }
+
+    fn poll_events(&mut self) -> Option<Vec<KeyEvent>> {
+        let now = self.clock.now();
+        let start = *self.start.get_or_insert(now);
+        let elapsed = now.saturating_duration_since(start);
+        let mut events = Vec::new();
+        while let Some(recorded) = self.keys.get(self.next).filter(|key| key.time() <= elapsed) {
+            if recorded.is_down {
+                self.pressed_keys.insert(recorded.key);
+            } else {
+                self.pressed_keys.remove(&recorded.key);
+            }
+            events.push(recorded.to_key_event(start));
+            self.next += 1;
+        }
+        Some(events)
+    }
+
+    /// end of recording once every key is replayed
+    fn check(&mut self) -> io::Result<()> {
+        if self.is_finished() {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "recording is replayed",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Reads JSON lines recording, empty lines are skipped.
+pub fn read_recording(path: impl AsRef<Path>) -> io::Result<Vec<RecordedKey>> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut keys = Vec::new();
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let key = serde_json::from_str(&line).map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("line {}: {}", i + 1, err),
+            )
+        })?;
+        keys.push(key);
+    }
+    Ok(keys)
+}
+
+/// Decodes recording as fast as possible, text is written as by text sinks.
+/// Same recording always decodes to same text for same config, so timings may be tuned on it.
+pub fn decode_recording(config: &Config, keys: &[RecordedKey]) -> String {
+    let start = Instant::now();
+    let inputs = keys
+        .iter()
+        .map(|key| DecoderInput::from(key.to_key_event(start)));
+    decode_inputs(config.clone(), inputs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::morse::audio::keying;
+    use crate::morse::{ChannelKeySource, FakeClock, Timing};
+    use std::sync::mpsc;
+
+    #[test]
+    fn recording_decodes_after_reading_back() {
+        let config = Config::default_config();
+        let clock = FakeClock::default();
+        let start = clock.now();
+        let (sender, receiver) = mpsc::channel();
+        let path = std::env::temp_dir().join(format!("morse-record-{}.jsonl", std::process::id()));
+        let file = File::create(&path).unwrap();
+        let mut source = RecordingKeySource::new(ChannelKeySource::new(receiver), file, &clock);
+
+        let sequences = config.encode("en", "hello world").unwrap();
+        let mut time = start;
+        for segment in keying(&sequences, &Timing::new(20.0)) {
+            let is_down = segment.is_down;
+            sender
+                .send(KeyEvent {
+                    key: config.main,
+                    is_down,
+                    time,
+                })
+                .unwrap();
+            time += segment.duration;
+        }
+        sender
+            .send(KeyEvent {
+                key: config.main,
+                is_down: false,
+                time,
+            })
+            .unwrap();
+        let events = source.poll_events().unwrap();
+        assert!(source.check().is_ok());
+        drop(source);
+
+        let keys = read_recording(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let recorded = keys
+            .iter()
+            .map(|key| key.to_key_event(start))
+            .collect::<Vec<_>>();
+        assert_eq!(recorded, events);
+        assert_eq!(decode_recording(&config, &keys), "hello world");
+    }
+
+    struct FailingWriter;
+
+    impl Write for FailingWriter {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::Error::new(io::ErrorKind::StorageFull, "disk is full"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn recording_error_is_checked_once() {
+        let clock = FakeClock::default();
+        let (sender, receiver) = mpsc::channel();
+        let mut source =
+            RecordingKeySource::new(ChannelKeySource::new(receiver), FailingWriter, &clock);
+        let event = KeyEvent {
+            key: KeyCode::Space,
+            is_down: true,
+            time: clock.now(),
+        };
+        sender.send(event).unwrap();
+
+        // keys still pass through
+        assert_eq!(source.poll_events(), Some(vec![event]));
+        let err = source.check().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::StorageFull);
+        assert!(source.check().is_ok());
+    }
+
+    #[test]
+    fn replay_ends_with_eof() {
+        let clock = FakeClock::default();
+        let key = |is_down, time_us| RecordedKey {
+            key: KeyCode::Space,
+            is_down,
+            time_us,
+        };
+        let mut source = ReplayKeySource::new(vec![key(true, 0), key(false, 50_000)], &clock);
+
+        assert_eq!(source.poll_events().map(|events| events.len()), Some(1));
+        assert!(source.check().is_ok());
+        assert!(source.is_key_down(KeyCode::Space));
+        clock.advance(Duration::from_millis(50));
+        assert_eq!(source.poll_events().map(|events| events.len()), Some(1));
+        assert!(source.is_finished());
+        let err = source.check().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
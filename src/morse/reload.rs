@@ -0,0 +1,168 @@
+use super::{check_durations, Config, ConfigError, ConfigSource, Severity};
+use std::fmt;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
+
+/// how often config file modification time is checked
+pub(crate) const CHECK_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Values replacing ones of loaded config (e.g. from command line), kept on reload.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigOverrides {
+    pub time_to_long_press: Option<Duration>,
+    pub accept_sequence_delay: Option<Duration>,
+    pub listen_delay: Option<Duration>,
+    pub lang: Option<String>,
+}
+
+impl ConfigOverrides {
+    /// config is left unchanged if overridden durations are zero
+    pub fn apply(&self, config: &mut Config) -> Result<(), ConfigError> {
+        check_durations(
+            self.time_to_long_press.unwrap_or(config.time_to_long_press),
+            self.accept_sequence_delay
+                .unwrap_or(config.accept_sequence_delay),
+        )?;
+        if let Some(time_to_long_press) = self.time_to_long_press {
+            config.time_to_long_press = time_to_long_press;
+        }
+        if let Some(accept_sequence_delay) = self.accept_sequence_delay {
+            config.accept_sequence_delay = accept_sequence_delay;
+        }
+        if let Some(listen_delay) = self.listen_delay {
+            config.listen_delay = listen_delay;
+        }
+        if let Some(lang) = &self.lang {
+            config.lang = Some(lang.clone());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum ReloadError {
+    Config(ConfigError),
+    /// config is loaded, but has issues of `Severity::Error`
+    Invalid(Vec<String>),
+}
+
+impl fmt::Display for ReloadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReloadError::Config(err) => write!(f, "{}", err),
+            ReloadError::Invalid(issues) => write!(f, "{}", issues.join("; ")),
+        }
+    }
+}
+
+impl std::error::Error for ReloadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ReloadError::Config(err) => Some(err),
+            ReloadError::Invalid(_) => None,
+        }
+    }
+}
+
+/// Reloads config file once its modification time changes.
+///
+/// Built-in config never changes, so nothing is watched for `ConfigSource::Default`.
+#[derive(Debug, Clone)]
+pub struct ConfigWatcher {
+    path: Option<PathBuf>,
+    overrides: ConfigOverrides,
+    modified: Option<SystemTime>,
+    last_check: Option<Instant>,
+}
+
+impl ConfigWatcher {
+    /// should be created right after config is loaded, so earlier changes are not missed
+    pub fn new(source: &ConfigSource, overrides: ConfigOverrides) -> Self {
+        let path = match source {
+            ConfigSource::File(path) => Some(path.clone()),
+            ConfigSource::Default => None,
+        };
+        let mut watcher = ConfigWatcher {
+            path,
+            overrides,
+            modified: None,
+            last_check: None,
+        };
+        watcher.modified = watcher.modified_time();
+        watcher
+    }
+
+    fn modified_time(&self) -> Option<SystemTime> {
+        let path = self.path.as_ref()?;
+        std::fs::metadata(path).and_then(|m| m.modified()).ok()
+    }
+
+    /// Loads and validates changed config, at most once per `CHECK_INTERVAL`.
+    ///
+    /// Config that failed to load is not retried until file is modified again.
+    pub fn poll(&mut self, now: Instant) -> Option<Result<Config, ReloadError>> {
+        let is_check_time = self
+            .last_check
+            .is_none_or(|last_check| now.saturating_duration_since(last_check) >= CHECK_INTERVAL);
+        if !is_check_time {
+            return None;
+        }
+        self.last_check = Some(now);
+
+        let modified = self.modified_time();
+        if modified.is_none() || modified == self.modified {
+            return None;
+        }
+        self.modified = modified;
+        Some(self.load())
+    }
+
+    fn load(&self) -> Result<Config, ReloadError> {
+        let path = self.path.as_ref().expect("only files are watched");
+        let mut config = Config::load(path).map_err(ReloadError::Config)?;
+        self.overrides
+            .apply(&mut config)
+            .map_err(ReloadError::Config)?;
+
+        let errors = config
+            .validate()
+            .into_iter()
+            .filter(|issue| issue.severity() == Severity::Error)
+            .map(|issue| issue.to_string())
+            .collect::<Vec<_>>();
+        if !errors.is_empty() {
+            return Err(ReloadError::Invalid(errors));
+        }
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ConfigOverrides;
+    use crate::morse::discover::DEFAULT_CONFIG;
+    use crate::morse::{Config, ConfigError};
+    use std::time::Duration;
+
+    #[test]
+    fn zero_durations_are_rejected() {
+        let source = DEFAULT_CONFIG.replacen("nanos = 100000000", "nanos = 0", 1);
+        assert!(matches!(
+            Config::from_toml(&source),
+            Err(ConfigError::ZeroDuration("time_to_long_press"))
+        ));
+
+        let mut config = Config::default_config();
+        let overrides = ConfigOverrides {
+            accept_sequence_delay: Some(Duration::ZERO),
+            lang: Some("ru".into()),
+            ..ConfigOverrides::default()
+        };
+        assert!(matches!(
+            overrides.apply(&mut config),
+            Err(ConfigError::ZeroDuration("accept_sequence_delay"))
+        ));
+        assert_eq!(config.accept_sequence_delay, Duration::from_millis(750));
+        assert_eq!(config.lang.as_deref(), Some("en"));
+    }
+}
@@ -0,0 +1,282 @@
+use super::audio::{self, Tone};
+use std::f64::consts::PI;
+use std::io;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+/// Sine generator of keyed tone, sounds while key is down.
+///
+/// Key changes do not cut tone: it rises and falls by half of cosine over `tone.ramp`,
+/// so there are no key clicks. Phase is continuous between keyings.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sidetone {
+    tone: Tone,
+    sample_rate: u32,
+    is_keyed: bool,
+    /// ramp progress, 0 is silence and 1 is full volume
+    envelope: f64,
+    /// radians
+    phase: f64,
+}
+
+impl Sidetone {
+    pub fn new(tone: Tone, sample_rate: u32) -> Self {
+        Sidetone {
+            tone,
+            sample_rate,
+            is_keyed: false,
+            envelope: 0.0,
+            phase: 0.0,
+        }
+    }
+
+    pub fn tone(&self) -> &Tone {
+        &self.tone
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn is_keyed(&self) -> bool {
+        self.is_keyed
+    }
+
+    pub fn set_keyed(&mut self, is_keyed: bool) {
+        self.is_keyed = is_keyed;
+    }
+
+    /// Writes next mono samples.
+    pub fn fill(&mut self, samples: &mut [f32]) {
+        let sample_rate = self.sample_rate as f64;
+        let ramp = self.tone.ramp.as_secs_f64() * sample_rate;
+        let envelope_step = if ramp < 1.0 { 1.0 } else { 1.0 / ramp };
+        let phase_step = 2.0 * PI * self.tone.frequency / sample_rate;
+        for sample in samples {
+            self.envelope = if self.is_keyed {
+                (self.envelope + envelope_step).min(1.0)
+            } else {
+                (self.envelope - envelope_step).max(0.0)
+            };
+            let gain = 0.5 * (1.0 - (PI * self.envelope).cos());
+            *sample = (self.tone.volume * gain * self.phase.sin()) as f32;
+            self.phase = (self.phase + phase_step) % (2.0 * PI);
+        }
+    }
+}
+
+/// Receives `InputEvent::KeyingChange`s.
+pub trait SidetoneSink {
+    fn set_keyed(&mut self, is_down: bool, time: Instant);
+
+    /// called once listening is over
+    fn finish(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// no sidetone
+pub struct NullSidetone;
+
+impl SidetoneSink for NullSidetone {
+    fn set_keyed(&mut self, _is_down: bool, _time: Instant) {}
+}
+
+/// Renders sidetone by times of keying changes and writes it to WAV file on finish,
+/// audio starts at first key down.
+pub struct WavSidetone {
+    path: PathBuf,
+    sidetone: Sidetone,
+    start: Option<Instant>,
+    samples: Vec<f32>,
+}
+
+impl WavSidetone {
+    pub fn new(path: impl Into<PathBuf>, tone: Tone, sample_rate: u32) -> Self {
+        WavSidetone {
+            path: path.into(),
+            sidetone: Sidetone::new(tone, sample_rate),
+            start: None,
+            samples: Vec::new(),
+        }
+    }
+
+    pub fn samples(&self) -> &[f32] {
+        &self.samples
+    }
+
+    /// renders samples until `time`, nothing if they are already rendered
+    fn render_until(&mut self, time: Instant) {
+        let start = *self.start.get_or_insert(time);
+        let elapsed = time.saturating_duration_since(start);
+        let count = (elapsed.as_secs_f64() * self.sidetone.sample_rate() as f64).round() as usize;
+        if count > self.samples.len() {
+            let rendered = self.samples.len();
+            self.samples.resize(count, 0.0);
+            self.sidetone.fill(&mut self.samples[rendered..]);
+        }
+    }
+}
+
+impl SidetoneSink for WavSidetone {
+    fn set_keyed(&mut self, is_down: bool, time: Instant) {
+        if self.start.is_none() && !is_down {
+            return;
+        }
+        self.render_until(time);
+        self.sidetone.set_keyed(is_down);
+    }
+
+    /// last key up fades out
+    fn finish(&mut self) -> io::Result<()> {
+        if let Some(start) = self.start {
+            let rendered = self.samples.len() as f64 / self.sidetone.sample_rate() as f64;
+            let end = start + Duration::from_secs_f64(rendered) + self.sidetone.tone().ramp;
+            self.render_until(end);
+        }
+        audio::write_wav(&self.path, &self.samples, self.sidetone.sample_rate())
+    }
+}
+
+/// Sidetone on default output device, keying changes sound once they are received.
+#[cfg(feature = "sidetone")]
+pub struct DeviceSidetone {
+    sidetone: std::sync::Arc<std::sync::Mutex<Sidetone>>,
+    _stream: cpal::Stream,
+}
+
+#[cfg(feature = "sidetone")]
+impl DeviceSidetone {
+    pub fn new(tone: Tone) -> io::Result<Self> {
+        use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+
+        let device = cpal::default_host()
+            .default_output_device()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no output device"))?;
+        let supported = device.default_output_config().map_err(io::Error::other)?;
+        let sample_format = supported.sample_format();
+        let config = cpal::StreamConfig::from(supported);
+        let sidetone = std::sync::Arc::new(std::sync::Mutex::new(Sidetone::new(
+            tone,
+            config.sample_rate.0,
+        )));
+        let stream = match sample_format {
+            cpal::SampleFormat::F32 => build_stream::<f32>(&device, &config, sidetone.clone()),
+            cpal::SampleFormat::I16 => build_stream::<i16>(&device, &config, sidetone.clone()),
+            cpal::SampleFormat::U16 => build_stream::<u16>(&device, &config, sidetone.clone()),
+            format => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    format!("unsupported sample format {}", format),
+                ))
+            }
+        }?;
+        stream.play().map_err(io::Error::other)?;
+        Ok(DeviceSidetone {
+            sidetone,
+            _stream: stream,
+        })
+    }
+}
+
+/// mono sidetone copied to every channel
+#[cfg(feature = "sidetone")]
+fn build_stream<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    sidetone: std::sync::Arc<std::sync::Mutex<Sidetone>>,
+) -> io::Result<cpal::Stream>
+where
+    T: cpal::SizedSample + cpal::FromSample<f32>,
+{
+    use cpal::traits::DeviceTrait;
+
+    let channels = config.channels.max(1) as usize;
+    let mut mono = Vec::new();
+    device
+        .build_output_stream(
+            config,
+            move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+                mono.resize(data.len() / channels, 0.0);
+                if let Ok(mut sidetone) = sidetone.lock() {
+                    sidetone.fill(&mut mono);
+                }
+                for (frame, &sample) in data.chunks_mut(channels).zip(&mono) {
+                    frame.fill(T::from_sample(sample));
+                }
+            },
+            |err| eprintln!("sidetone stream error: {}", err),
+            None,
+        )
+        .map_err(io::Error::other)
+}
+
+#[cfg(feature = "sidetone")]
+impl SidetoneSink for DeviceSidetone {
+    fn set_keyed(&mut self, is_down: bool, _time: Instant) {
+        if let Ok(mut sidetone) = self.sidetone.lock() {
+            sidetone.set_keyed(is_down);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Sidetone, SidetoneSink, WavSidetone};
+    use crate::morse::audio::Tone;
+    use std::time::{Duration, Instant};
+
+    const SAMPLE_RATE: u32 = 8000;
+
+    /// ramp of 40 samples
+    fn tone() -> Tone {
+        Tone {
+            frequency: 600.0,
+            volume: 0.5,
+            ramp: Duration::from_millis(5),
+        }
+    }
+
+    fn peak(samples: &[f32]) -> f32 {
+        samples
+            .iter()
+            .fold(0.0, |peak, sample| peak.max(sample.abs()))
+    }
+
+    #[test]
+    fn fill_ramps_tone_up_and_down() {
+        let mut sidetone = Sidetone::new(tone(), SAMPLE_RATE);
+        let mut samples = [0.0; 400];
+        sidetone.fill(&mut samples);
+        assert_eq!(peak(&samples), 0.0);
+
+        sidetone.set_keyed(true);
+        sidetone.fill(&mut samples);
+        assert!(peak(&samples[..4]) < 0.05);
+        assert!(peak(&samples[40..]) > 0.49 && peak(&samples[40..]) <= 0.5);
+
+        sidetone.set_keyed(false);
+        sidetone.fill(&mut samples);
+        assert!(peak(&samples[..4]) > 0.45);
+        assert_eq!(peak(&samples[40..]), 0.0);
+    }
+
+    #[test]
+    fn wav_sidetone_renders_by_keying_times() {
+        let path = std::env::temp_dir().join("morse-sidetone-test.wav");
+        let mut sidetone = WavSidetone::new(path, tone(), SAMPLE_RATE);
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+
+        // audio starts at first key down
+        sidetone.set_keyed(false, at(0));
+        sidetone.set_keyed(true, at(100));
+        sidetone.set_keyed(false, at(200));
+        sidetone.set_keyed(true, at(400));
+
+        let samples = sidetone.samples();
+        assert_eq!(samples.len(), 2400);
+        assert!(peak(&samples[400..800]) > 0.49);
+        assert_eq!(peak(&samples[840..]), 0.0);
+    }
+}
@@ -0,0 +1,267 @@
+use super::KeyCode;
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+use std::io;
+use std::sync::mpsc::{Receiver, RecvTimeoutError, TryRecvError};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub key: KeyCode,
+    /// true - pressed, false - released
+    pub is_down: bool,
+    pub time: Instant,
+}
+
+/// Provides key states to `listen_loop`.
+///
+/// Sources either answer "is this key down" queries (OS key state, GPIO and so on)
+/// or push press/release events with their own timestamps (serial keyers, network feeds).
+pub trait KeySource {
+    /// true if key is held right now
+    fn is_key_down(&mut self, key: KeyCode) -> bool;
+
+    /// Press and release events happened since previous call, in order of occurrence.
+    ///
+    /// Returns none for sources that can only be queried, `listen_loop` then polls
+    /// `is_key_down` for every configured key once per `listen_delay`.
+    fn poll_events(&mut self) -> Option<Vec<KeyEvent>> {
+        None
+    }
+
+    /// Blocks until events are pending or `timeout` passes.
+    ///
+    /// Returns false right away if source can not wait for events,
+    /// `listen_loop` then sleeps `listen_delay` between polls.
+    fn wait_events(&mut self, _timeout: Duration) -> bool {
+        false
+    }
+
+    /// Keys `listen_loop` is interested in, called before first poll and after config reload.
+    fn listen_keys(&mut self, _keys: &[KeyCode]) {}
+
+    /// Error once source can not report keys anymore (e.g. every device is unplugged),
+    /// `listen_loop` then stops with it. Called after every poll.
+    fn check(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<S: KeySource + ?Sized> KeySource for Box<S> {
+    fn is_key_down(&mut self, key: KeyCode) -> bool {
+        (**self).is_key_down(key)
+    }
+
+    fn poll_events(&mut self) -> Option<Vec<KeyEvent>> {
+        (**self).poll_events()
+    }
+
+    fn wait_events(&mut self, timeout: Duration) -> bool {
+        (**self).wait_events(timeout)
+    }
+
+    fn listen_keys(&mut self, keys: &[KeyCode]) {
+        (**self).listen_keys(keys)
+    }
+
+    fn check(&mut self) -> io::Result<()> {
+        (**self).check()
+    }
+}
+
+/// Event source fed from another thread (or a test) through a channel.
+pub struct ChannelKeySource {
+    receiver: Receiver<KeyEvent>,
+    /// received while waiting
+    pending: Vec<KeyEvent>,
+    pressed_keys: HashSet<KeyCode>,
+}
+
+impl ChannelKeySource {
+    pub fn new(receiver: Receiver<KeyEvent>) -> Self {
+        ChannelKeySource {
+            receiver,
+            pending: Vec::new(),
+            pressed_keys: HashSet::new(),
+        }
+    }
+}
+
+impl KeySource for ChannelKeySource {
+    /// last reported state of key, keys are released before first event
+    fn is_key_down(&mut self, key: KeyCode) -> bool {
+        self.pressed_keys.contains(&key)
+    }
+
+    fn poll_events(&mut self) -> Option<Vec<KeyEvent>> {
+        let mut events = std::mem::take(&mut self.pending);
+        events.extend(self.receiver.try_iter());
+        for event in &events {
+            if event.is_down {
+                self.pressed_keys.insert(event.key);
+            } else {
+                self.pressed_keys.remove(&event.key);
+            }
+        }
+        Some(events)
+    }
+
+    /// false once sender is dropped
+    fn wait_events(&mut self, timeout: Duration) -> bool {
+        wait_pending(&self.receiver, &mut self.pending, timeout)
+    }
+
+    /// error once sender is dropped and its events are polled
+    fn check(&mut self) -> io::Result<()> {
+        if is_closed(&self.receiver, &mut self.pending) {
+            return Err(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "key event sender is gone",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Raw key change of event-driven source: key of device or OS, down or up, time.
+pub type RawKeyEvent<R> = (R, bool, Instant);
+
+/// Event source of raw keys pushed by reader threads (evdev devices, keyboard hooks).
+/// Threads send their error before they stop.
+///
+/// Listened key goes down with first of its raw keys and up with last one,
+/// e.g. `Shift` with left and right shift. Keys are down only while they are listened.
+pub struct EventKeySource<R> {
+    receiver: Receiver<io::Result<RawKeyEvent<R>>>,
+    pending: Vec<io::Result<RawKeyEvent<R>>>,
+    /// last error of reader threads
+    error: Option<io::Error>,
+    raw_keys_of: fn(KeyCode) -> Vec<R>,
+    /// raw keys of listened keys
+    raw_keys: HashMap<KeyCode, Vec<R>>,
+    pressed_raw_keys: HashSet<R>,
+    pressed_keys: HashSet<KeyCode>,
+}
+
+impl<R: Copy + Eq + Hash> EventKeySource<R> {
+    /// `raw_keys_of` gives raw keys that press listened key
+    pub fn new(
+        receiver: Receiver<io::Result<RawKeyEvent<R>>>,
+        raw_keys_of: fn(KeyCode) -> Vec<R>,
+    ) -> Self {
+        EventKeySource {
+            receiver,
+            pending: Vec::new(),
+            error: None,
+            raw_keys_of,
+            raw_keys: HashMap::new(),
+            pressed_raw_keys: HashSet::new(),
+            pressed_keys: HashSet::new(),
+        }
+    }
+
+    fn handle_raw(&mut self, (raw_key, is_down, time): RawKeyEvent<R>, events: &mut Vec<KeyEvent>) {
+        let is_changed = if is_down {
+            self.pressed_raw_keys.insert(raw_key)
+        } else {
+            self.pressed_raw_keys.remove(&raw_key)
+        };
+        if !is_changed {
+            // autorepeat
+            return;
+        }
+        for (&key, raw_keys) in &self.raw_keys {
+            if !raw_keys.contains(&raw_key) {
+                continue;
+            }
+            let is_key_down = raw_keys
+                .iter()
+                .any(|raw_key| self.pressed_raw_keys.contains(raw_key));
+            let is_key_changed = if is_key_down {
+                self.pressed_keys.insert(key)
+            } else {
+                self.pressed_keys.remove(&key)
+            };
+            if is_key_changed {
+                events.push(KeyEvent {
+                    key,
+                    is_down: is_key_down,
+                    time,
+                });
+            }
+        }
+    }
+}
+
+impl<R: Copy + Eq + Hash> KeySource for EventKeySource<R> {
+    fn is_key_down(&mut self, key: KeyCode) -> bool {
+        self.pressed_keys.contains(&key)
+    }
+
+    fn poll_events(&mut self) -> Option<Vec<KeyEvent>> {
+        let mut raw_events = std::mem::take(&mut self.pending);
+        raw_events.extend(self.receiver.try_iter());
+        let mut events = Vec::new();
+        for raw_event in raw_events {
+            match raw_event {
+                Ok(raw_event) => self.handle_raw(raw_event, &mut events),
+                Err(err) => self.error = Some(err),
+            }
+        }
+        Some(events)
+    }
+
+    /// false once all reader threads are gone (e.g. devices are unplugged)
+    fn wait_events(&mut self, timeout: Duration) -> bool {
+        wait_pending(&self.receiver, &mut self.pending, timeout)
+    }
+
+    /// last error of reader threads once all of them are gone
+    fn check(&mut self) -> io::Result<()> {
+        if is_closed(&self.receiver, &mut self.pending) {
+            return Err(self.error.take().unwrap_or_else(|| {
+                io::Error::new(io::ErrorKind::BrokenPipe, "key reader threads are gone")
+            }));
+        }
+        Ok(())
+    }
+
+    fn listen_keys(&mut self, keys: &[KeyCode]) {
+        self.raw_keys = keys
+            .iter()
+            .map(|&key| (key, (self.raw_keys_of)(key)))
+            .collect();
+        self.pressed_keys.retain(|key| keys.contains(key));
+    }
+}
+
+/// blocks for first event of `receiver` and keeps it in `pending`, false if senders are gone
+fn wait_pending<T>(receiver: &Receiver<T>, pending: &mut Vec<T>, timeout: Duration) -> bool {
+    if !pending.is_empty() {
+        return true;
+    }
+    match receiver.recv_timeout(timeout) {
+        Ok(event) => {
+            pending.push(event);
+            true
+        }
+        Err(RecvTimeoutError::Timeout) => true,
+        Err(RecvTimeoutError::Disconnected) => false,
+    }
+}
+
+/// true if senders of `receiver` are gone and all of their events are taken from `pending`,
+/// event received meanwhile is kept in `pending`
+fn is_closed<T>(receiver: &Receiver<T>, pending: &mut Vec<T>) -> bool {
+    if !pending.is_empty() {
+        return false;
+    }
+    match receiver.try_recv() {
+        Ok(event) => {
+            pending.push(event);
+            false
+        }
+        Err(TryRecvError::Empty) => false,
+        Err(TryRecvError::Disconnected) => true,
+    }
+}
@@ -0,0 +1,163 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::Duration;
+
+/// seconds of dot unit at 1 wpm, "PARIS " is 50 units long
+const PARIS_UNIT_SECS: f64 = 1.2;
+/// units of "PARIS " between elements: 31 for characters, 19 for spacing
+const PARIS_CHARACTER_UNITS: f64 = 31.0;
+const PARIS_SPACING_UNITS: f64 = 19.0;
+
+/// Speed in words per minute by PARIS standard.
+///
+/// With Farnsworth timing characters are sent at `wpm`, but gaps between characters
+/// and words are stretched so whole text is sent at `farnsworth_wpm`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Timing {
+    /// character speed
+    pub wpm: f64,
+    /// effective speed, same as `wpm` if not set
+    pub farnsworth_wpm: Option<f64>,
+}
+
+impl Timing {
+    pub fn new(wpm: f64) -> Self {
+        Timing {
+            wpm,
+            farnsworth_wpm: None,
+        }
+    }
+
+    /// false if speeds are not positive or effective speed is above character one
+    pub fn is_valid(&self) -> bool {
+        self.wpm.is_finite()
+            && self.wpm > 0.0
+            && self
+                .farnsworth_wpm
+                .is_none_or(|wpm| wpm.is_finite() && wpm > 0.0 && wpm <= self.wpm)
+    }
+
+    /// dot duration
+    pub fn unit(&self) -> Duration {
+        Duration::from_secs_f64(PARIS_UNIT_SECS / self.wpm)
+    }
+
+    /// Gap unit: character gap is 3 and word gap is 7 of them.
+    /// Same as `unit` without Farnsworth timing.
+    pub fn spacing_unit(&self) -> Duration {
+        match self.farnsworth_wpm {
+            Some(farnsworth_wpm) if farnsworth_wpm < self.wpm => {
+                // time of spacing in one word minus time of characters at `wpm`
+                let word_secs = 60.0 / farnsworth_wpm;
+                let characters_secs = PARIS_CHARACTER_UNITS * self.unit().as_secs_f64();
+                Duration::from_secs_f64((word_secs - characters_secs) / PARIS_SPACING_UNITS)
+            }
+            _ => self.unit(),
+        }
+    }
+
+    /// gap stretch of Farnsworth timing, 1 without it
+    pub fn spacing_ratio(&self) -> f64 {
+        self.spacing_unit().as_secs_f64() / self.unit().as_secs_f64()
+    }
+
+    /// threshold between dot (1 unit) and dash (3 units)
+    pub fn time_to_long_press(&self) -> Duration {
+        self.unit() * 2
+    }
+
+    /// threshold between element gap (1 unit) and character gap (3 spacing units)
+    pub fn accept_sequence_delay(&self) -> Duration {
+        (self.unit() + self.spacing_unit() * 3) / 2
+    }
+
+    /// Inverse of `time_to_long_press` and `accept_sequence_delay`, so configs with raw
+    /// durations can be expressed in wpm. Farnsworth speed is set only if gaps are stretched,
+    /// gaps shorter than standard ones are lost.
+    pub fn from_durations(time_to_long_press: Duration, accept_sequence_delay: Duration) -> Self {
+        let unit = time_to_long_press.as_secs_f64() / 2.0;
+        let wpm = PARIS_UNIT_SECS / unit;
+        let spacing_unit = (accept_sequence_delay.as_secs_f64() * 2.0 - unit) / 3.0;
+        // nanosecond rounding of durations must not turn into Farnsworth timing
+        let farnsworth_wpm = (spacing_unit - unit > 1e-6)
+            .then(|| 60.0 / (PARIS_CHARACTER_UNITS * unit + PARIS_SPACING_UNITS * spacing_unit));
+        Timing {
+            wpm,
+            farnsworth_wpm,
+        }
+    }
+}
+
+impl fmt::Display for Timing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.1} wpm", self.wpm)?;
+        if let Some(farnsworth_wpm) = self.farnsworth_wpm {
+            write!(f, " (Farnsworth {:.1} wpm)", farnsworth_wpm)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Timing;
+    use std::time::Duration;
+
+    fn assert_close(left: f64, right: f64) {
+        assert!((left - right).abs() < 1e-6, "{} != {}", left, right);
+    }
+
+    fn assert_round_trip(timing: Timing) {
+        let back =
+            Timing::from_durations(timing.time_to_long_press(), timing.accept_sequence_delay());
+        assert_close(back.wpm, timing.wpm);
+        match (back.farnsworth_wpm, timing.farnsworth_wpm) {
+            (Some(back), Some(farnsworth_wpm)) => assert_close(back, farnsworth_wpm),
+            (back, farnsworth_wpm) => assert_eq!(back, farnsworth_wpm),
+        }
+    }
+
+    #[test]
+    fn timing_round_trips_through_durations() {
+        assert_round_trip(Timing::new(20.0));
+        assert_round_trip(Timing::new(7.5));
+        assert_round_trip(Timing {
+            wpm: 18.0,
+            farnsworth_wpm: Some(5.0),
+        });
+    }
+
+    #[test]
+    fn durations_round_trip_through_timing() {
+        for (time_to_long_press, accept_sequence_delay) in [(100, 750), (100, 100), (120, 200)] {
+            let time_to_long_press = Duration::from_millis(time_to_long_press);
+            let accept_sequence_delay = Duration::from_millis(accept_sequence_delay);
+            let timing = Timing::from_durations(time_to_long_press, accept_sequence_delay);
+            assert_close(
+                timing.time_to_long_press().as_secs_f64(),
+                time_to_long_press.as_secs_f64(),
+            );
+            assert_close(
+                timing.accept_sequence_delay().as_secs_f64(),
+                accept_sequence_delay.as_secs_f64(),
+            );
+        }
+    }
+
+    #[test]
+    fn standard_gaps_are_not_farnsworth() {
+        // accept_sequence_delay of 2 units is standard character gap threshold
+        let timing = Timing::from_durations(Duration::from_millis(100), Duration::from_millis(100));
+        assert_eq!(timing.farnsworth_wpm, None);
+        assert_close(timing.spacing_ratio(), 1.0);
+        assert_eq!(timing.to_string(), "24.0 wpm");
+    }
+
+    #[test]
+    fn built_in_durations_are_farnsworth() {
+        let timing = Timing::from_durations(Duration::from_millis(100), Duration::from_millis(750));
+        assert_eq!(timing.to_string(), "24.0 wpm (Farnsworth 5.6 wpm)");
+        // character gap of 3 spacing units is 1450 ms
+        assert_close(timing.spacing_unit().as_secs_f64() * 3.0, 1.45);
+    }
+}
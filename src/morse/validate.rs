@@ -0,0 +1,362 @@
+use super::{morse_seq_to_string, Config, ConfigKey, KeyCode, MorseSequence};
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// config works, but probably not as intended
+    Warning,
+    /// some input can not work with this config
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigIssue {
+    /// functional sequences are looked up first, so lang one is never produced
+    ShadowedSequence {
+        lang: String,
+        sequence: MorseSequence,
+        functional: ConfigKey,
+        shadowed: ConfigKey,
+    },
+    /// several control keys (`main`, `exit`, `dit`, ...) share one key
+    ConflictingControls {
+        key: KeyCode,
+        controls: Vec<&'static str>,
+    },
+    /// every sequence is rejected
+    NoLangs,
+    /// `lang` is not in `langs`, any other lang is used on start
+    UnknownLang(String),
+    EmptyLang(String),
+    /// empty sequence can not be entered
+    EmptySequence {
+        lang: Option<String>,
+    },
+    /// several sequences produce same key
+    DuplicateKey {
+        /// none for `functional` sequences
+        lang: Option<String>,
+        key: ConfigKey,
+        sequences: Vec<MorseSequence>,
+    },
+}
+
+impl ConfigIssue {
+    pub fn severity(&self) -> Severity {
+        match self {
+            ConfigIssue::ConflictingControls { .. } | ConfigIssue::NoLangs => Severity::Error,
+            ConfigIssue::ShadowedSequence { .. }
+            | ConfigIssue::UnknownLang(_)
+            | ConfigIssue::EmptyLang(_)
+            | ConfigIssue::EmptySequence { .. }
+            | ConfigIssue::DuplicateKey { .. } => Severity::Warning,
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+impl fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: ", self.severity())?;
+        match self {
+            ConfigIssue::ShadowedSequence {
+                lang,
+                sequence,
+                functional,
+                shadowed,
+            } => write!(
+                f,
+                "sequence \"{}\" of lang {:?} ({:?}) is shadowed by functional one ({:?})",
+                morse_seq_to_string(sequence),
+                lang,
+                shadowed,
+                functional
+            ),
+            ConfigIssue::ConflictingControls { key, controls } => {
+                write!(f, "{:?} is bound to {}", key, controls.join(", "))
+            }
+            ConfigIssue::NoLangs => write!(f, "no langs defined"),
+            ConfigIssue::UnknownLang(lang) => write!(f, "start lang {:?} is not defined", lang),
+            ConfigIssue::EmptyLang(lang) => write!(f, "lang {:?} has no sequences", lang),
+            ConfigIssue::EmptySequence { lang } => match lang {
+                Some(lang) => write!(f, "lang {:?} has empty sequence", lang),
+                None => write!(f, "functional keys have empty sequence"),
+            },
+            ConfigIssue::DuplicateKey {
+                lang,
+                key,
+                sequences,
+            } => {
+                let sequences = sequences
+                    .iter()
+                    .map(|seq| format!("\"{}\"", morse_seq_to_string(seq)))
+                    .collect::<Vec<_>>();
+                write!(f, "{:?} is produced by {}", key, sequences.join(", "))?;
+                match lang {
+                    Some(lang) => write!(f, " of lang {:?}", lang),
+                    None => write!(f, " of functional keys"),
+                }
+            }
+        }
+    }
+}
+
+impl Config {
+    /// Finds shadowed, duplicate and conflicting definitions, errors go first.
+    pub fn validate(&self) -> Vec<ConfigIssue> {
+        let mut issues = Vec::new();
+
+        // main key is not used by paddle keyers
+        let main = (!self.keyer.is_paddle()).then_some(("main", self.main));
+        let controls = main
+            .into_iter()
+            .chain([
+                ("exit", self.exit),
+                ("pause", self.pause),
+                ("change_lang", self.change_lang),
+                ("change_case", self.change_case),
+            ])
+            .chain(self.dit.map(|dit| ("dit", dit)))
+            .chain(self.dah.map(|dah| ("dah", dah)))
+            .collect::<Vec<_>>();
+        for (i, (_, key)) in controls.iter().enumerate() {
+            // report each conflict once, at its first control
+            if controls[..i].iter().any(|(_, other)| other == key) {
+                continue;
+            }
+            let conflicting = controls[i..]
+                .iter()
+                .filter(|(_, other)| other == key)
+                .map(|(other_name, _)| *other_name)
+                .collect::<Vec<_>>();
+            if conflicting.len() > 1 {
+                issues.push(ConfigIssue::ConflictingControls {
+                    key: *key,
+                    controls: conflicting,
+                });
+            }
+        }
+
+        if self.langs.is_empty() {
+            issues.push(ConfigIssue::NoLangs);
+        }
+        if let Some(lang) = &self.lang {
+            if !self.langs.contains_key(lang) {
+                issues.push(ConfigIssue::UnknownLang(lang.clone()));
+            }
+        }
+
+        let mut langs = self.langs.iter().collect::<Vec<_>>();
+        langs.sort_by_key(|(lang, _)| *lang);
+        for (lang, keys) in langs {
+            if keys.is_empty() {
+                issues.push(ConfigIssue::EmptyLang(lang.clone()));
+            }
+            let mut sequences = keys.iter().collect::<Vec<_>>();
+            sequences.sort_by_key(|(seq, _)| morse_seq_to_string(seq));
+            for (sequence, key) in sequences {
+                if let Some(functional) = self.functional.get(sequence) {
+                    issues.push(ConfigIssue::ShadowedSequence {
+                        lang: lang.clone(),
+                        sequence: sequence.clone(),
+                        functional: functional.clone(),
+                        shadowed: key.clone(),
+                    });
+                }
+            }
+            check_sequences(Some(lang), keys, &mut issues);
+        }
+        check_sequences(None, &self.functional, &mut issues);
+
+        // stable sort keeps order of definitions within one severity
+        issues.sort_by_key(|issue| std::cmp::Reverse(issue.severity()));
+        issues
+    }
+}
+
+/// empty and duplicate sequences of one table
+fn check_sequences(
+    lang: Option<&String>,
+    keys: &HashMap<MorseSequence, ConfigKey>,
+    issues: &mut Vec<ConfigIssue>,
+) {
+    if keys.contains_key(&Vec::new()) {
+        issues.push(ConfigIssue::EmptySequence {
+            lang: lang.cloned(),
+        });
+    }
+
+    let mut sequences_by_key: HashMap<&ConfigKey, Vec<MorseSequence>> = HashMap::new();
+    for (sequence, key) in keys {
+        sequences_by_key
+            .entry(key)
+            .or_default()
+            .push(sequence.clone());
+    }
+    let mut duplicates = sequences_by_key
+        .into_iter()
+        .filter(|(_, sequences)| sequences.len() > 1)
+        .map(|(key, mut sequences)| {
+            sequences.sort_by_key(|seq| morse_seq_to_string(seq));
+            (key, sequences)
+        })
+        .collect::<Vec<_>>();
+    duplicates.sort_by_key(|(_, sequences)| morse_seq_to_string(&sequences[0]));
+    for (key, sequences) in duplicates {
+        issues.push(ConfigIssue::DuplicateKey {
+            lang: lang.cloned(),
+            key: key.clone(),
+            sequences,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ConfigIssue, Severity};
+    use crate::morse::{morse_seq_from_string, Config, ConfigKey, ConfigLayoutKey, KeyCode};
+    use crate::morse::{KeyerMode, MorseSequence};
+
+    fn seq(sequence: &str) -> MorseSequence {
+        morse_seq_from_string(sequence).unwrap()
+    }
+
+    fn layout(character: char) -> ConfigKey {
+        ConfigKey::Layout(ConfigLayoutKey {
+            lower: KeyCode::Layout(character),
+        })
+    }
+
+    #[test]
+    fn built_in_config_has_no_issues() {
+        assert_eq!(Config::default_config().validate(), []);
+    }
+
+    #[test]
+    fn shadowed_sequences_are_warned_by_lang() {
+        let mut config = Config::default_config();
+        // functional Backspace and "."
+        for (lang, sequence) in [("ru", ".-.--"), ("en", "......")] {
+            let keys = config.langs.get_mut(lang).unwrap();
+            keys.insert(seq(sequence), layout('~'));
+        }
+        let issues = config.validate();
+        assert_eq!(
+            issues,
+            [
+                ConfigIssue::ShadowedSequence {
+                    lang: "en".into(),
+                    sequence: seq("......"),
+                    functional: layout('.'),
+                    shadowed: layout('~'),
+                },
+                ConfigIssue::ShadowedSequence {
+                    lang: "ru".into(),
+                    sequence: seq(".-.--"),
+                    functional: ConfigKey::Code(KeyCode::Backspace),
+                    shadowed: layout('~'),
+                },
+            ]
+        );
+        assert_eq!(issues[0].severity(), Severity::Warning);
+    }
+
+    #[test]
+    fn conflicting_controls_are_errors() {
+        let mut config = Config::default_config();
+        config.exit = config.main;
+        config.pause = config.main;
+        let issues = config.validate();
+        assert_eq!(
+            issues,
+            [ConfigIssue::ConflictingControls {
+                key: KeyCode::Space,
+                controls: vec!["main", "exit", "pause"],
+            }]
+        );
+        assert_eq!(issues[0].severity(), Severity::Error);
+        assert_eq!(
+            issues[0].to_string(),
+            "error: Space is bound to main, exit, pause"
+        );
+    }
+
+    #[test]
+    fn main_is_not_a_control_of_paddle_keyers() {
+        let mut config = Config::default_config();
+        config.keyer = KeyerMode::IambicA;
+        config.dit = Some(config.main);
+        config.dah = Some(KeyCode::Layout('x'));
+        assert_eq!(config.validate(), []);
+
+        config.dah = Some(config.exit);
+        assert_eq!(
+            config.validate(),
+            [ConfigIssue::ConflictingControls {
+                key: KeyCode::Escape,
+                controls: vec!["exit", "dah"],
+            }]
+        );
+
+        // manual contact of bug is `dah`, `main` is still unused
+        config.keyer = KeyerMode::Bug;
+        config.dah = Some(KeyCode::Layout('x'));
+        assert_eq!(config.validate(), []);
+    }
+
+    #[test]
+    fn missing_and_empty_langs_are_reported() {
+        let mut config = Config::default_config();
+        config.langs.clear();
+        assert_eq!(
+            config.validate(),
+            [ConfigIssue::NoLangs, ConfigIssue::UnknownLang("en".into())]
+        );
+
+        let mut config = Config::default_config();
+        config.langs.insert("de".into(), Default::default());
+        assert_eq!(config.validate(), [ConfigIssue::EmptyLang("de".into())]);
+    }
+
+    #[test]
+    fn errors_go_before_warnings() {
+        let mut config = Config::default_config();
+        config
+            .langs
+            .get_mut("en")
+            .unwrap()
+            .insert(Vec::new(), layout('~'));
+        config.functional.insert(seq("........"), layout('.'));
+        config.change_case = config.change_lang;
+        let issues = config.validate();
+        assert_eq!(
+            issues,
+            [
+                ConfigIssue::ConflictingControls {
+                    key: KeyCode::Control,
+                    controls: vec!["change_lang", "change_case"],
+                },
+                ConfigIssue::EmptySequence {
+                    lang: Some("en".into()),
+                },
+                ConfigIssue::DuplicateKey {
+                    lang: None,
+                    key: layout('.'),
+                    sequences: vec![seq("......"), seq("........")],
+                },
+            ]
+        );
+        assert!(issues
+            .windows(2)
+            .all(|pair| pair[0].severity() >= pair[1].severity()));
+    }
+}
@@ -0,0 +1,96 @@
+use super::key_code_into_virtual_key;
+use super::source::{EventKeySource, RawKeyEvent};
+use std::io;
+use std::sync::mpsc::{self, Sender};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+use windows::core::PCWSTR;
+use windows::Win32::Foundation::{HWND, LPARAM, LRESULT, WPARAM};
+use windows::Win32::System::LibraryLoader::GetModuleHandleW;
+use windows::Win32::System::SystemInformation::GetTickCount;
+use windows::Win32::UI::Input::KeyboardAndMouse::*;
+use windows::Win32::UI::WindowsAndMessaging::{
+    CallNextHookEx, GetMessageW, SetWindowsHookExW, HHOOK, KBDLLHOOKSTRUCT, LLKHF_INJECTED, MSG,
+    WH_KEYBOARD_LL, WM_KEYDOWN, WM_KEYUP, WM_SYSKEYDOWN, WM_SYSKEYUP,
+};
+
+/// older event timestamps mean that hook thread was stalled and tick count is not trusted
+const MAX_EVENT_AGE: Duration = Duration::from_secs(1);
+
+/// hook procedure has no user data, so its events go to last opened source
+static SENDER: Mutex<Option<Sender<io::Result<RawKeyEvent<u16>>>>> = Mutex::new(None);
+
+/// Low-level keyboard hook (`SetWindowsHookEx`) in its own message loop thread,
+/// keys are timed by their event timestamps instead of polling `GetAsyncKeyState`.
+///
+/// Injected keys (e.g. separators typed by `EnigoSink`) are skipped, they are not operator's.
+///
+/// Only one hook source receives events: opening another one takes them over.
+pub fn open_hook_keyboard() -> io::Result<EventKeySource<u16>> {
+    let (sender, receiver) = mpsc::channel();
+    *SENDER.lock().unwrap_or_else(|err| err.into_inner()) = Some(sender);
+
+    let (result_sender, result_receiver) = mpsc::channel();
+    thread::spawn(move || unsafe {
+        let hook = GetModuleHandleW(PCWSTR::null())
+            .and_then(|module| SetWindowsHookExW(WH_KEYBOARD_LL, Some(hook_proc), module, 0));
+        let is_hooked = hook.is_ok();
+        let _ = result_sender.send(hook.map(|_| ()));
+        if !is_hooked {
+            return;
+        }
+        // hook is called through messages of thread that installed it
+        let mut message = MSG::default();
+        while GetMessageW(&mut message, HWND::default(), 0, 0).as_bool() {}
+    });
+    result_receiver
+        .recv()
+        .map_err(|_| io::Error::other("keyboard hook thread failed"))?
+        .map_err(io::Error::other)?;
+    Ok(EventKeySource::new(receiver, virtual_keys))
+}
+
+unsafe extern "system" fn hook_proc(code: i32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    let info = &*(lparam.0 as *const KBDLLHOOKSTRUCT);
+    if code >= 0 && (info.flags & LLKHF_INJECTED).0 == 0 {
+        let is_down = match wparam.0 as u32 {
+            WM_KEYDOWN | WM_SYSKEYDOWN => Some(true),
+            WM_KEYUP | WM_SYSKEYUP => Some(false),
+            _ => None,
+        };
+        if let (Some(is_down), Ok(sender)) = (is_down, SENDER.lock()) {
+            if let Some(sender) = sender.as_ref() {
+                let _ = sender.send(Ok((info.vkCode as u16, is_down, event_instant(info.time))));
+            }
+        }
+    }
+    CallNextHookEx(HHOOK::default(), code, wparam, lparam)
+}
+
+/// Event timestamps are milliseconds of `GetTickCount`, they are turned into `Instant` by their age.
+fn event_instant(timestamp: u32) -> Instant {
+    let now = Instant::now();
+    let now_ticks = unsafe { GetTickCount() };
+    let age = Duration::from_millis(now_ticks.wrapping_sub(timestamp) as u64);
+    match now.checked_sub(age) {
+        Some(time) if age <= MAX_EVENT_AGE => time,
+        _ => now,
+    }
+}
+
+/// hook reports left and right modifiers, generic ones match both like `GetAsyncKeyState` does
+fn virtual_keys(key: super::KeyCode) -> Vec<u16> {
+    let virtual_key = key_code_into_virtual_key(key);
+    let sides = match virtual_key {
+        VK_SHIFT => vec![VK_LSHIFT, VK_RSHIFT],
+        VK_CONTROL => vec![VK_LCONTROL, VK_RCONTROL],
+        VK_MENU => vec![VK_LMENU, VK_RMENU],
+        _ => Vec::new(),
+    };
+    [virtual_key]
+        .into_iter()
+        .chain(sides)
+        .map(|virtual_key| virtual_key.0)
+        .collect()
+}